@@ -0,0 +1,442 @@
+//! What the player can do, and the keys and controller buttons bound to
+//! each action. Game systems ask [`Actions`] whether an action is pressed
+//! rather than reading keys or controllers.
+
+use bevy::ecs::system::SystemParam;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use crate::settings::Settings;
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadActions>()
+            .add_systems(PreUpdate, read_gamepads.after(InputSystem));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCcw,
+    RotateCw,
+    Rotate180,
+    Hold,
+    Pause,
+    ToggleTheme,
+    ToggleInstructions,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCcw,
+        Action::RotateCw,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Pause,
+        Action::ToggleTheme,
+        Action::ToggleInstructions,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::SoftDrop => "Soft Drop",
+            Action::HardDrop => "Hard Drop",
+            Action::RotateCcw => "Rotate Left",
+            Action::RotateCw => "Rotate Right",
+            Action::Rotate180 => "Rotate 180",
+            Action::Hold => "Hold",
+            Action::Pause => "Pause",
+            Action::ToggleTheme => "Switch Theme",
+            Action::ToggleInstructions => "Show Controls",
+        }
+    }
+}
+
+/// A key or button that actions can be bound to.
+pub trait Binding: Copy + PartialEq + 'static {
+    /// What `action` is bound to out of the box.
+    fn defaults(action: Action) -> &'static [Self];
+
+    /// A short name for the player to read, such as `A`, `1` or `Left`.
+    fn name(self) -> String;
+}
+
+impl Binding for KeyCode {
+    fn defaults(action: Action) -> &'static [Self] {
+        match action {
+            Action::MoveLeft => &[KeyCode::KeyA, KeyCode::ArrowLeft],
+            Action::MoveRight => &[KeyCode::KeyD, KeyCode::ArrowRight],
+            Action::SoftDrop => &[KeyCode::KeyS, KeyCode::ArrowDown],
+            Action::HardDrop => &[KeyCode::Space],
+            Action::RotateCcw => &[KeyCode::KeyQ, KeyCode::KeyZ],
+            Action::RotateCw => &[KeyCode::KeyE, KeyCode::KeyX, KeyCode::ArrowUp],
+            Action::Rotate180 => &[KeyCode::KeyW],
+            Action::Hold => &[KeyCode::KeyC, KeyCode::ShiftLeft],
+            Action::Pause => &[KeyCode::Escape],
+            Action::ToggleTheme => &[KeyCode::KeyL],
+            Action::ToggleInstructions => &[KeyCode::Tab],
+        }
+    }
+
+    fn name(self) -> String {
+        let name = format!("{self:?}");
+
+        ["Key", "Digit", "Arrow"]
+            .into_iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .unwrap_or(&name)
+            .to_string()
+    }
+}
+
+impl Binding for GamepadButton {
+    fn defaults(action: Action) -> &'static [Self] {
+        match action {
+            Action::MoveLeft => &[GamepadButton::DPadLeft],
+            Action::MoveRight => &[GamepadButton::DPadRight],
+            Action::SoftDrop => &[GamepadButton::DPadDown],
+            Action::HardDrop => &[GamepadButton::North, GamepadButton::DPadUp],
+            Action::RotateCcw => &[GamepadButton::East],
+            Action::RotateCw => &[GamepadButton::South],
+            Action::Rotate180 => &[GamepadButton::RightTrigger],
+            Action::Hold => &[GamepadButton::West, GamepadButton::LeftTrigger],
+            Action::Pause => &[GamepadButton::Start],
+            Action::ToggleTheme => &[],
+            Action::ToggleInstructions => &[GamepadButton::Select],
+        }
+    }
+
+    fn name(self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// What each action is bound to. An action can have any number of keys or
+/// buttons, but each of them only ever triggers one action.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(
+    from = "BTreeMap<Action, Vec<B>>",
+    into = "BTreeMap<Action, Vec<B>>",
+    bound(
+        serialize = "B: Binding + Serialize",
+        deserialize = "B: Binding + Deserialize<'de>"
+    )
+)]
+pub struct Bindings<B: Binding = KeyCode>(BTreeMap<Action, Vec<B>>);
+
+impl<B: Binding> Default for Bindings<B> {
+    fn default() -> Self {
+        Bindings(
+            Action::ALL
+                .into_iter()
+                .map(|action| (action, B::defaults(action).to_vec()))
+                .collect(),
+        )
+    }
+}
+
+impl<B: Binding> From<Bindings<B>> for BTreeMap<Action, Vec<B>> {
+    fn from(bindings: Bindings<B>) -> Self {
+        bindings.0
+    }
+}
+
+/// Actions missing from the settings file keep their defaults.
+impl<B: Binding> From<BTreeMap<Action, Vec<B>>> for Bindings<B> {
+    fn from(keys: BTreeMap<Action, Vec<B>>) -> Self {
+        let mut bindings = Bindings::default();
+        bindings.0.extend(keys);
+        bindings
+    }
+}
+
+impl<B: Binding> Bindings<B> {
+    pub fn keys(&self, action: Action) -> &[B] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action that `key` is bound to, if any.
+    pub fn action(&self, key: B) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|&action| self.keys(action).contains(&key))
+    }
+
+    /// Binds `key` to `action` as well as its other keys. Fails with the
+    /// action the key is already bound to, if it is a different one.
+    pub fn bind(&mut self, action: Action, key: B) -> Result<(), Action> {
+        match self.action(key) {
+            Some(bound) if bound == action => Ok(()),
+            Some(bound) => Err(bound),
+            None => {
+                self.0.entry(action).or_default().push(key);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.0.insert(action, Vec::new());
+    }
+
+    /// The first key found bound to two actions, along with both actions.
+    pub fn conflict(&self) -> Option<(B, Action, Action)> {
+        Action::ALL.into_iter().find_map(|action| {
+            self.keys(action).iter().find_map(|&key| {
+                let first = self.action(key)?;
+                (first != action).then_some((key, first, action))
+            })
+        })
+    }
+
+    /// Lists the keys bound to `action` for the player to read.
+    pub fn describe(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return "Unbound".to_string();
+        }
+
+        keys.iter()
+            .map(|&key| key.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// How a controller is bound to the player's actions. Besides its buttons,
+/// the left stick moves and soft drops.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadBindings {
+    /// How far the left stick has to be pushed before it counts, from 0
+    /// to 1.
+    pub deadzone: f32,
+    pub buttons: Bindings<GamepadButton>,
+}
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        GamepadBindings {
+            deadzone: 0.5,
+            buttons: Bindings::default(),
+        }
+    }
+}
+
+impl GamepadBindings {
+    fn pressed(&self, gamepad: &Gamepad, action: Action) -> bool {
+        let stick = gamepad.left_stick();
+        let pushed = match action {
+            Action::MoveLeft => stick.x < -self.deadzone,
+            Action::MoveRight => stick.x > self.deadzone,
+            Action::SoftDrop => stick.y < -self.deadzone,
+            _ => false,
+        };
+
+        pushed || gamepad.any_pressed(self.buttons.keys(action).iter().copied())
+    }
+}
+
+/// The actions held on any connected controller. Kept as its own input so
+/// that pushing the stick is just pressed for a frame, like a button.
+#[derive(Resource, Default)]
+struct GamepadActions(ButtonInput<Action>);
+
+fn read_gamepads(
+    gamepads: Query<(&Gamepad, Option<&Name>)>,
+    settings: Res<Settings>,
+    mut actions: ResMut<GamepadActions>,
+) {
+    actions.0.clear();
+
+    for action in Action::ALL {
+        let pressed = gamepads.iter().any(|(gamepad, name)| {
+            let name = name.map_or("", Name::as_str);
+            settings.gamepad_bindings(name).pressed(gamepad, action)
+        });
+
+        if pressed {
+            actions.0.press(action);
+        } else {
+            actions.0.release(action);
+        }
+    }
+}
+
+/// Whether the player's actions are pressed, whichever of their keys or
+/// controllers they are pressed with. Keyboard and controller movement
+/// share the same auto shift, since the engine only sees what is held.
+#[derive(SystemParam)]
+pub struct Actions<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    gamepads: Res<'w, GamepadActions>,
+    settings: Res<'w, Settings>,
+}
+
+impl Actions<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.keys
+            .any_pressed(self.settings.bindings.keys(action).iter().copied())
+            || self.gamepads.0.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.keys
+            .any_just_pressed(self.settings.bindings.keys(action).iter().copied())
+            || self.gamepads.0.just_pressed(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::input::InputPlugin;
+    use bevy::input::gamepad::{
+        GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent,
+        RawGamepadButtonChangedEvent, RawGamepadEvent,
+    };
+
+    #[test]
+    fn binds_keys_without_conflicts() {
+        let mut bindings = Bindings::default();
+
+        assert_eq!(bindings.bind(Action::Hold, KeyCode::KeyH), Ok(()));
+        assert_eq!(bindings.keys(Action::Hold).last(), Some(&KeyCode::KeyH));
+        assert_eq!(bindings.bind(Action::Hold, KeyCode::KeyH), Ok(()));
+        assert_eq!(
+            bindings.bind(Action::Hold, KeyCode::KeyA),
+            Err(Action::MoveLeft)
+        );
+        assert_eq!(bindings.conflict(), None);
+
+        bindings.clear(Action::Hold);
+        assert_eq!(bindings.describe(Action::Hold), "Unbound");
+    }
+
+    #[test]
+    fn names_keys() {
+        assert_eq!(KeyCode::KeyA.name(), "A");
+        assert_eq!(KeyCode::Digit1.name(), "1");
+        assert_eq!(KeyCode::ArrowLeft.name(), "Left");
+        assert_eq!(KeyCode::Space.name(), "Space");
+    }
+
+    fn gamepad_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins((InputPlugin, ActionsPlugin))
+            .insert_resource(Settings::default());
+
+        let gamepad = app.world_mut().spawn_empty().id();
+        app.world_mut().send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected {
+                name: "Test Controller".to_string(),
+                vendor_id: None,
+                product_id: None,
+            },
+        ));
+        app.update();
+
+        (app, gamepad)
+    }
+
+    fn pressed(app: &mut App, action: Action) -> (bool, bool) {
+        app.world_mut()
+            .run_system_once(move |actions: Actions| {
+                (actions.pressed(action), actions.just_pressed(action))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_gamepad_buttons() {
+        let (mut app, gamepad) = gamepad_app();
+
+        app.world_mut()
+            .send_event(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                gamepad,
+                GamepadButton::South,
+                1.0,
+            )))
+            .unwrap();
+        app.update();
+        assert_eq!(pressed(&mut app, Action::RotateCw), (true, true));
+        assert_eq!(pressed(&mut app, Action::RotateCcw), (false, false));
+
+        app.update();
+        assert_eq!(pressed(&mut app, Action::RotateCw), (true, false));
+    }
+
+    #[test]
+    fn reads_the_stick_past_its_deadzone() {
+        let (mut app, gamepad) = gamepad_app();
+        let push = |app: &mut App, x: f32| {
+            app.world_mut()
+                .send_event(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(
+                    gamepad,
+                    GamepadAxis::LeftStickX,
+                    x,
+                )))
+                .unwrap();
+            app.update();
+        };
+
+        push(&mut app, -0.3);
+        assert_eq!(pressed(&mut app, Action::MoveLeft), (false, false));
+
+        push(&mut app, -0.8);
+        assert_eq!(pressed(&mut app, Action::MoveLeft), (true, true));
+
+        push(&mut app, -0.9);
+        assert_eq!(pressed(&mut app, Action::MoveLeft), (true, false));
+
+        push(&mut app, 0.8);
+        assert_eq!(pressed(&mut app, Action::MoveLeft), (false, false));
+        assert_eq!(pressed(&mut app, Action::MoveRight), (true, true));
+    }
+
+    #[test]
+    fn binds_controllers_by_name() {
+        let (mut app, gamepad) = gamepad_app();
+
+        let mut bindings = GamepadBindings::default();
+        bindings.buttons.clear(Action::RotateCw);
+        bindings
+            .buttons
+            .bind(Action::Hold, GamepadButton::South)
+            .unwrap();
+        app.world_mut()
+            .resource_mut::<Settings>()
+            .controllers
+            .insert("Test Controller".to_string(), bindings);
+
+        app.world_mut()
+            .send_event(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(
+                gamepad,
+                GamepadButton::South,
+                1.0,
+            )))
+            .unwrap();
+        app.update();
+        assert_eq!(pressed(&mut app, Action::Hold), (true, true));
+        assert_eq!(pressed(&mut app, Action::RotateCw), (false, false));
+    }
+}
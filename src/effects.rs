@@ -0,0 +1,276 @@
+//! Particles and camera shake that give hard drops and line clears some
+//! weight. None of it touches the game, and all of it can be turned off.
+
+use bevy::prelude::*;
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use tetris_rust::engine::{self, GRID_HEIGHT, GRID_WIDTH};
+
+use crate::settings::Settings;
+use crate::{EngineEvent, GameState, InGame, Theme};
+
+/// Particles left behind in each column per row a piece is hard dropped,
+/// at full intensity.
+const TRAIL_PER_ROW: f32 = 0.5;
+/// Particles each tile of a cleared row bursts into per line cleared, at
+/// full intensity.
+const BURST_PER_LINE: f32 = 1.5;
+/// How far the camera is knocked by a hard drop at full intensity, in
+/// tiles.
+const SHAKE_DISTANCE: f32 = 0.2;
+/// How long the camera takes to settle after a hard drop, in seconds.
+const SHAKE_TIME: f32 = 0.15;
+/// How fast particles fall, in tiles a second squared.
+const PARTICLE_GRAVITY: f32 = 30.0;
+
+/// How the effects play, or whether they play at all.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Effects {
+    pub enabled: bool,
+    /// How many particles are thrown and how hard the camera shakes, where
+    /// 1 is the usual amount.
+    pub intensity: f32,
+    /// Turns every effect off, whatever the rest of these settings say.
+    pub reduce_motion: bool,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Effects {
+            enabled: true,
+            intensity: 1.0,
+            reduce_motion: false,
+        }
+    }
+}
+
+impl Effects {
+    /// The intensity effects play at, which is 0 while they are off.
+    pub fn strength(&self) -> f32 {
+        if self.enabled && !self.reduce_motion {
+            self.intensity
+        } else {
+            0.0
+        }
+    }
+}
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Shake>()
+            .add_systems(
+                Update,
+                (spawn_effects, move_particles)
+                    .run_if(in_state(GameState::Running).or(in_state(GameState::Replay))),
+            )
+            // the camera settles even once the game is paused or over
+            .add_systems(Update, shake_camera);
+    }
+}
+
+#[derive(Component)]
+struct Particle {
+    /// In pixels a second.
+    velocity: Vec2,
+    life: Timer,
+}
+
+/// How far the camera is still knocked from the center, in tiles.
+#[derive(Resource, Default)]
+struct Shake(f32);
+
+/// Where the center of the tile at `tile` is drawn.
+fn tile_position(tile: IVec2, block_size: f32) -> Vec2 {
+    Vec2::new(
+        tile.x as f32 - GRID_WIDTH as f32 / 2.0,
+        tile.y as f32 - GRID_HEIGHT as f32 / 2.0,
+    ) * block_size
+}
+
+/// How many particles to throw for `amount` at full intensity. Fractions
+/// round at random, so small amounts still throw some now and then.
+fn particle_count(amount: f32, rng: &mut impl Rng) -> u32 {
+    let whole = amount.floor();
+    whole as u32 + u32::from(rng.random::<f32>() < amount - whole)
+}
+
+fn spawn_particle(
+    commands: &mut Commands,
+    position: Vec2,
+    velocity: Vec2,
+    color: Color,
+    size: f32,
+    life: f32,
+) {
+    commands.spawn((
+        InGame,
+        Particle {
+            velocity,
+            life: Timer::from_seconds(life, TimerMode::Once),
+        },
+        Sprite {
+            color,
+            custom_size: Some(Vec2::splat(size)),
+            ..default()
+        },
+        // in front of the board and its pieces
+        Transform::from_translation(position.extend(2.0)),
+    ));
+}
+
+fn spawn_effects(
+    mut commands: Commands,
+    mut events: EventReader<EngineEvent>,
+    theme: Res<Theme>,
+    settings: Res<Settings>,
+    mut shake: ResMut<Shake>,
+) {
+    let strength = settings.effects.strength();
+    if strength <= 0.0 {
+        events.clear();
+        return;
+    }
+
+    let block_size = settings.block_size;
+    let mut rng = rand::rng();
+    // a hard drop is only known to have locked once the lock follows it,
+    // as training can send the piece back to the top instead
+    let mut dropped = None;
+
+    for EngineEvent(event) in events.read() {
+        match event {
+            engine::Event::HardDropped(rows) if *rows > 0 => dropped = Some(*rows),
+            engine::Event::PieceLocked(lock) => {
+                let piece = &lock.piece;
+                let color = theme.color(piece.color());
+                let tiles = piece.occupied_tiles();
+
+                if let Some(rows) = dropped.take() {
+                    // a trail rises above the top of each column the piece fell down
+                    for &top in tiles
+                        .iter()
+                        .filter(|&&tile| !tiles.contains(&(tile + IVec2::Y)))
+                    {
+                        for _ in 0..particle_count(rows as f32 * TRAIL_PER_ROW * strength, &mut rng)
+                        {
+                            let offset = Vec2::new(
+                                rng.random_range(-0.5..0.5),
+                                rng.random_range(0.5..rows as f32 + 0.5),
+                            );
+
+                            spawn_particle(
+                                &mut commands,
+                                tile_position(top, block_size) + offset * block_size,
+                                Vec2::new(0.0, rng.random_range(2.0..6.0)) * block_size,
+                                color,
+                                block_size * rng.random_range(0.1..0.25),
+                                rng.random_range(0.15..0.35),
+                            );
+                        }
+                    }
+
+                    shake.0 = shake.0.max(SHAKE_DISTANCE * strength);
+                }
+
+                let lines = (lock.cleared.len() / GRID_WIDTH as usize) as f32;
+                for &(tile, color_name) in &lock.cleared {
+                    let color = theme.color(color_name);
+
+                    for _ in 0..particle_count(lines * BURST_PER_LINE * strength, &mut rng) {
+                        let direction =
+                            Vec2::from_angle(rng.random_range(0.0..std::f32::consts::TAU));
+                        let speed = rng.random_range(0.5..1.0) * (4.0 + 2.0 * lines);
+
+                        spawn_particle(
+                            &mut commands,
+                            tile_position(tile, block_size),
+                            direction * speed * block_size,
+                            color,
+                            block_size * rng.random_range(0.15..0.3),
+                            rng.random_range(0.4..0.8),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn move_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let delta = time.delta_secs();
+
+    for (entity, mut particle, mut transform, mut sprite) in &mut particles {
+        if particle.life.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= PARTICLE_GRAVITY * settings.block_size * delta;
+        transform.translation += particle.velocity.extend(0.0) * delta;
+        sprite.color.set_alpha(particle.life.fraction_remaining());
+    }
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut shake: ResMut<Shake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    // turning effects off stops a shake already under way
+    if settings.effects.strength() <= 0.0 {
+        shake.0 = 0.0;
+    }
+
+    shake.0 = (shake.0 - SHAKE_DISTANCE * time.delta_secs() / SHAKE_TIME).max(0.0);
+
+    let offset = if shake.0 > 0.0 {
+        let direction = Vec2::from_angle(rand::rng().random_range(0.0..std::f32::consts::TAU));
+        direction * shake.0 * settings.block_size
+    } else {
+        Vec2::ZERO
+    };
+
+    for mut transform in &mut cameras {
+        if transform.translation.truncate() != offset {
+            transform.translation = offset.extend(transform.translation.z);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduce_motion_turns_effects_off() {
+        let effects = Effects {
+            intensity: 1.5,
+            ..Effects::default()
+        };
+        assert_eq!(effects.strength(), 1.5);
+
+        let reduced = Effects {
+            reduce_motion: true,
+            ..effects
+        };
+        assert_eq!(reduced.strength(), 0.0);
+
+        let disabled = Effects {
+            enabled: false,
+            ..effects
+        };
+        assert_eq!(disabled.strength(), 0.0);
+    }
+}
@@ -0,0 +1,262 @@
+use std::time::Duration;
+
+/// The player's feel settings for held inputs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Handling {
+    /// Delayed Auto Shift: how long a direction is held before it repeats.
+    pub das: Duration,
+    /// Auto Repeat Rate: time between repeated shifts once DAS has charged.
+    /// Zero teleports the piece to the wall.
+    pub arr: Duration,
+    /// Soft Drop Factor: how many times faster than gravity a held soft drop
+    /// falls. `u32::MAX` drops to the floor instantly.
+    pub soft_drop_factor: u32,
+    /// How long a charged DAS waits before repeating again when a new piece
+    /// spawns. Zero carries the charge straight over.
+    pub das_cut: Duration,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: Duration::from_millis(167),
+            arr: Duration::from_millis(33),
+            soft_drop_factor: 20,
+            das_cut: Duration::ZERO,
+        }
+    }
+}
+
+/// The inputs that act for as long as they are held down.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Held {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+}
+
+impl Held {
+    fn direction(&self, direction: Direction) -> bool {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+impl Direction {
+    const fn opposite(self) -> Self {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// Tracks the most recently pressed direction and how long it has been held.
+#[derive(Default)]
+pub(super) struct AutoShift {
+    direction: Option<Direction>,
+    charge: Duration,
+    repeat: Duration,
+    cut: Duration,
+}
+
+/// A shift that moves the piece as far as it can go.
+const TO_WALL: u32 = u32::MAX;
+
+impl AutoShift {
+    /// Called on the initial press of a direction, which also shifts the
+    /// piece once on its own.
+    pub(super) fn press(&mut self, direction: Direction) {
+        *self = AutoShift {
+            direction: Some(direction),
+            ..AutoShift::default()
+        };
+    }
+
+    /// Called when a new piece spawns.
+    pub(super) fn cut(&mut self, handling: &Handling) {
+        if self.direction.is_some() && self.charge >= handling.das {
+            self.cut = handling.das_cut;
+        }
+    }
+
+    /// Advances the held direction by `delta` and returns how many cells the
+    /// piece should shift, if any.
+    pub(super) fn tick(
+        &mut self,
+        handling: &Handling,
+        held: Held,
+        delta: Duration,
+    ) -> Option<(Direction, u32)> {
+        let direction = self.direction?;
+
+        if !held.direction(direction) {
+            // fall back to the other direction if it is still held
+            let other = direction.opposite();
+            *self = AutoShift {
+                direction: held.direction(other).then_some(other),
+                ..AutoShift::default()
+            };
+            return None;
+        }
+
+        if self.cut > Duration::ZERO {
+            self.cut = self.cut.saturating_sub(delta);
+            return None;
+        }
+
+        let was_charged = self.charge >= handling.das;
+        self.charge += delta;
+
+        if self.charge < handling.das {
+            return None;
+        }
+
+        if handling.arr.is_zero() {
+            return Some((direction, TO_WALL));
+        }
+
+        let mut shifts = 0;
+        if was_charged {
+            self.repeat += delta;
+        } else {
+            // DAS just charged, which shifts once on its own
+            self.repeat = self.charge - handling.das;
+            shifts += 1;
+        }
+
+        while self.repeat >= handling.arr {
+            self.repeat -= handling.arr;
+            shifts += 1;
+        }
+
+        (shifts > 0).then_some((direction, shifts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLING: Handling = Handling {
+        das: Duration::from_millis(100),
+        arr: Duration::from_millis(20),
+        soft_drop_factor: 20,
+        das_cut: Duration::from_millis(50),
+    };
+
+    const LEFT: Held = Held {
+        left: true,
+        right: false,
+        soft_drop: false,
+    };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn charges_before_repeating() {
+        let mut autoshift = AutoShift::default();
+        autoshift.press(Direction::Left);
+
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(99)), None);
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(1)),
+            Some((Direction::Left, 1))
+        );
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(19)), None);
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(1)),
+            Some((Direction::Left, 1))
+        );
+        // a long frame catches up on every repeat it covered
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(60)),
+            Some((Direction::Left, 3))
+        );
+    }
+
+    #[test]
+    fn releasing_falls_back_to_the_other_direction() {
+        let mut autoshift = AutoShift::default();
+        autoshift.press(Direction::Left);
+        autoshift.press(Direction::Right);
+
+        let both = Held {
+            right: true,
+            ..LEFT
+        };
+        assert_eq!(
+            autoshift.tick(&HANDLING, both, ms(100)),
+            Some((Direction::Right, 1))
+        );
+
+        // letting go of right starts charging left afresh
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(50)), None);
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(100)),
+            Some((Direction::Left, 1))
+        );
+
+        assert_eq!(autoshift.tick(&HANDLING, Held::default(), ms(100)), None);
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(100)), None);
+    }
+
+    #[test]
+    fn zero_arr_shifts_to_the_wall() {
+        let handling = Handling {
+            arr: Duration::ZERO,
+            ..HANDLING
+        };
+        let mut autoshift = AutoShift::default();
+        autoshift.press(Direction::Left);
+
+        assert_eq!(autoshift.tick(&handling, LEFT, ms(50)), None);
+        assert_eq!(
+            autoshift.tick(&handling, LEFT, ms(50)),
+            Some((Direction::Left, TO_WALL))
+        );
+        assert_eq!(
+            autoshift.tick(&handling, LEFT, ms(1)),
+            Some((Direction::Left, TO_WALL))
+        );
+    }
+
+    #[test]
+    fn das_cut_pauses_a_charged_shift() {
+        let mut autoshift = AutoShift::default();
+        autoshift.press(Direction::Left);
+        assert!(autoshift.tick(&HANDLING, LEFT, ms(100)).is_some());
+
+        autoshift.cut(&HANDLING);
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(30)), None);
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(20)), None);
+        // the charge carries over once the cut is up
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(20)),
+            Some((Direction::Left, 1))
+        );
+    }
+
+    #[test]
+    fn das_cut_leaves_an_uncharged_shift_alone() {
+        let mut autoshift = AutoShift::default();
+        autoshift.press(Direction::Left);
+        assert_eq!(autoshift.tick(&HANDLING, LEFT, ms(60)), None);
+
+        autoshift.cut(&HANDLING);
+        assert_eq!(
+            autoshift.tick(&HANDLING, LEFT, ms(40)),
+            Some((Direction::Left, 1))
+        );
+    }
+}
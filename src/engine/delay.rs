@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+/// Pauses between one piece locking and the next one spawning. Modern games
+/// have none, while classic ones wait a few frames each time.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Delays {
+    /// How long full rows stay on the board before they are removed.
+    pub line_clear: Duration,
+    /// Entry delay, or ARE: how long the next piece waits to spawn after a
+    /// lock, or after full rows are removed.
+    pub entry: Duration,
+}
+
+/// A frame of a classic game running at 60 frames a second.
+const fn frames(count: u64) -> Duration {
+    Duration::from_nanos(count * 1_000_000_000 / 60)
+}
+
+impl Delays {
+    pub const NONE: Delays = Delays {
+        line_clear: Duration::ZERO,
+        entry: Duration::ZERO,
+    };
+
+    /// Roughly the NES game, whose entry delay really grows with the height
+    /// the piece locked at.
+    pub const NES: Delays = Delays {
+        line_clear: frames(18),
+        entry: frames(10),
+    };
+
+    /// The first Tetris: The Grand Master.
+    pub const TGM: Delays = Delays {
+        line_clear: frames(41),
+        entry: frames(30),
+    };
+
+    pub const PRESETS: [Delays; 3] = [Delays::NONE, Delays::NES, Delays::TGM];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Delays::NONE => "None",
+            Delays::NES => "NES",
+            Delays::TGM => "TGM",
+            _ => "Custom",
+        }
+    }
+}
+
+/// What the engine is waiting on between pieces.
+#[derive(Clone, PartialEq, Debug)]
+pub(super) enum Wait {
+    /// Full rows are shown for the line clear delay before being removed.
+    LineClear { rows: Vec<i32>, elapsed: Duration },
+    /// The next piece spawns once the entry delay is up.
+    Entry { elapsed: Duration },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{Engine, Held, Input, Ruleset};
+
+    #[test]
+    fn waits_before_the_next_piece() {
+        let ruleset = Ruleset {
+            delays: Delays::TGM,
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, 5);
+        let next = engine.queue().upcoming[0];
+
+        engine.apply(Input::HardDrop);
+        assert!(engine.is_between_pieces());
+        assert!(engine.apply(Input::HardDrop).is_empty());
+
+        engine.update(Delays::TGM.entry / 2, Held::default());
+        assert!(engine.is_between_pieces());
+
+        engine.update(Delays::TGM.entry / 2, Held::default());
+        assert!(!engine.is_between_pieces());
+        assert_eq!(engine.active().kind, next);
+        assert_eq!(engine.pieces(), 1);
+    }
+}
@@ -0,0 +1,307 @@
+//! Finesse: placing each piece with as few moves and rotations as possible.
+//!
+//! Finesse is judged on an empty board, from the spawn position to where
+//! the piece is dropped, so the stack never excuses extra inputs.
+
+use glam::IVec2;
+
+use std::collections::VecDeque;
+
+use super::piece::{Tetromino, TetrominoKind, get_wall_kick_offsets};
+
+/// A single press in a finesse sequence.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Move {
+    Left,
+    Right,
+    /// Holding left until the piece reaches the wall, which is one press
+    /// however far it goes.
+    DasLeft,
+    DasRight,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+}
+
+impl Move {
+    pub const ALL: [Move; 7] = [
+        Move::Left,
+        Move::Right,
+        Move::DasLeft,
+        Move::DasRight,
+        Move::RotateLeft,
+        Move::RotateRight,
+        Move::Rotate180,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Move::Left => "Left",
+            Move::Right => "Right",
+            Move::DasLeft => "DAS Left",
+            Move::DasRight => "DAS Right",
+            Move::RotateLeft => "Rotate Left",
+            Move::RotateRight => "Rotate Right",
+            Move::Rotate180 => "Rotate 180",
+        }
+    }
+
+    /// Where `piece` ends up after this move, or `None` if the walls stop
+    /// it from moving at all.
+    fn apply(self, piece: &Tetromino) -> Option<Tetromino> {
+        let mut moved = piece.clone();
+
+        match self {
+            Move::Left => moved.move_left(),
+            Move::Right => moved.move_right(),
+            Move::DasLeft | Move::DasRight => {
+                let step = if self == Move::DasLeft {
+                    Tetromino::move_left
+                } else {
+                    Tetromino::move_right
+                };
+
+                let mut next = moved.clone();
+                step(&mut next);
+                while !next.is_in_wall() {
+                    moved = next.clone();
+                    step(&mut next);
+                }
+
+                return (moved.position != piece.position).then_some(moved);
+            }
+            Move::RotateLeft | Move::RotateRight | Move::Rotate180 => {
+                match self {
+                    Move::RotateLeft => moved.rotate_left(),
+                    Move::RotateRight => moved.rotate_right(),
+                    _ => moved.rotate_180(),
+                }
+
+                let offsets = get_wall_kick_offsets(piece.kind, piece.rotation, moved.rotation);
+                return offsets.into_iter().find_map(|offset| {
+                    let mut kicked = moved.clone();
+                    kicked.position += offset;
+                    (!kicked.is_in_wall()).then_some(kicked)
+                });
+            }
+        }
+
+        (!moved.is_in_wall()).then_some(moved)
+    }
+}
+
+/// The tiles a piece covers relative to its lowest row and sorted, so that
+/// placements which land the same way compare equal, such as an S piece
+/// turned either way up.
+fn footprint(piece: &Tetromino) -> [IVec2; 4] {
+    let mut tiles = piece.occupied_tiles();
+    let bottom = tiles.iter().map(|tile| tile.y).min().unwrap_or_default();
+
+    for tile in &mut tiles {
+        tile.y -= bottom;
+    }
+    tiles.sort_by_key(|tile| (tile.x, tile.y));
+
+    tiles
+}
+
+/// A piece placed with more moves and rotations than it needed.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FinesseFault {
+    pub used: u32,
+    pub optimal: Vec<Move>,
+}
+
+/// Every placement that a freshly spawned piece of `kind` can be dropped
+/// from, each with the shortest sequence of moves that reaches it, not
+/// counting the drop itself.
+pub fn placements(kind: TetrominoKind) -> Vec<(Tetromino, Vec<Move>)> {
+    let start = Tetromino::new(kind);
+
+    let mut placements: Vec<(Tetromino, Vec<Move>)> = Vec::new();
+    let mut seen = vec![(start.position, start.rotation)];
+    let mut queue = VecDeque::from([(start, Vec::new())]);
+
+    // searching breadth first reaches each placement by a shortest sequence
+    while let Some((piece, moves)) = queue.pop_front() {
+        let landing = footprint(&piece);
+        if !placements
+            .iter()
+            .any(|(placed, _)| footprint(placed) == landing)
+        {
+            placements.push((piece.clone(), moves.clone()));
+        }
+
+        for step in Move::ALL {
+            let Some(next) = step.apply(&piece) else {
+                continue;
+            };
+
+            if seen.contains(&(next.position, next.rotation)) {
+                continue;
+            }
+            seen.push((next.position, next.rotation));
+
+            let mut next_moves = moves.clone();
+            next_moves.push(step);
+            queue.push_back((next, next_moves));
+        }
+    }
+
+    placements
+}
+
+/// The shortest sequence of moves that takes a freshly spawned piece of the
+/// same kind as `target` to a placement that lands the way `target` does.
+pub fn optimal_moves(target: &Tetromino) -> Option<Vec<Move>> {
+    let goal = footprint(target);
+
+    placements(target.kind)
+        .into_iter()
+        .find(|(placed, _)| footprint(placed) == goal)
+        .map(|(_, moves)| moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{
+        Engine, Event, GARBAGE_COLOR, GRID_WIDTH, GameMode, Input, Rotation, Ruleset, TETROMINOS,
+        TetrominoKind,
+    };
+
+    use glam::IVec2;
+
+    fn placement(kind: TetrominoKind, rotation: Rotation, x: i32) -> Tetromino {
+        let mut piece = Tetromino::new(kind);
+        piece.rotation = rotation;
+        piece.position.x = x;
+        piece
+    }
+
+    #[test]
+    fn finds_the_shortest_sequences() {
+        let moves = |kind, rotation, x| optimal_moves(&placement(kind, rotation, x)).unwrap();
+
+        assert_eq!(moves(TetrominoKind::T, Rotation::North, 5), []);
+        assert_eq!(moves(TetrominoKind::T, Rotation::North, 4), [Move::Left]);
+        assert_eq!(moves(TetrominoKind::T, Rotation::North, 1), [Move::DasLeft]);
+        assert_eq!(
+            moves(TetrominoKind::T, Rotation::North, 2),
+            [Move::DasLeft, Move::Right]
+        );
+        assert_eq!(
+            moves(TetrominoKind::T, Rotation::South, 5),
+            [Move::Rotate180]
+        );
+        // either way up, a vertical I lands the same
+        assert_eq!(
+            moves(TetrominoKind::I, Rotation::East, 8).last(),
+            Some(&Move::DasRight)
+        );
+        assert_eq!(moves(TetrominoKind::I, Rotation::East, 8).len(), 2);
+    }
+
+    #[test]
+    fn lists_every_distinct_placement() {
+        let count = |kind| placements(kind).len();
+
+        assert_eq!(count(TetrominoKind::I), 17);
+        assert_eq!(count(TetrominoKind::O), 9);
+        assert_eq!(count(TetrominoKind::S), 17);
+        assert_eq!(count(TetrominoKind::T), 34);
+    }
+
+    #[test]
+    fn treats_matching_placements_alike() {
+        let north = optimal_moves(&placement(TetrominoKind::S, Rotation::North, 5));
+        let south = optimal_moves(&placement(TetrominoKind::S, Rotation::South, 5));
+        assert_eq!(north, south);
+    }
+
+    #[test]
+    fn reaches_every_placement() {
+        for kind in TETROMINOS {
+            for rotation in [
+                Rotation::North,
+                Rotation::East,
+                Rotation::South,
+                Rotation::West,
+            ] {
+                for x in 0..GRID_WIDTH {
+                    let target = placement(kind, rotation, x);
+                    if target.is_in_wall() {
+                        continue;
+                    }
+
+                    let moves = optimal_moves(&target);
+                    assert!(
+                        moves.as_ref().is_some_and(|moves| moves.len() <= 4),
+                        "{kind:?} {rotation:?} {x}: {moves:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn restarts_faults_in_training() {
+        let ruleset = Ruleset {
+            mode: GameMode::Training { restart: true },
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, 3);
+        let kind = engine.active().kind;
+
+        engine.apply(Input::MoveLeft);
+        engine.apply(Input::MoveRight);
+        let events = engine.apply(Input::HardDrop);
+
+        assert!(events.contains(&Event::FinesseFault));
+        assert!(events.contains(&Event::PieceRestarted));
+        assert_eq!(engine.pieces(), 0);
+        assert_eq!(engine.active().kind, kind);
+        assert_eq!(engine.stats().finesse_faults, 1);
+        assert_eq!(engine.finesse_fault().map(|fault| fault.used), Some(2));
+
+        let events = engine.apply(Input::HardDrop);
+        assert!(
+            events
+                .iter()
+                .any(|event| matches!(event, Event::PieceLocked(_)))
+        );
+        assert_eq!(engine.pieces(), 1);
+    }
+
+    #[test]
+    fn judges_only_pieces_dropped_from_above() {
+        let ruleset = Ruleset {
+            mode: GameMode::Training { restart: false },
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, 3);
+
+        let events = engine.apply(Input::HardDrop);
+        assert!(events.contains(&Event::FinesseClean));
+        assert!(!events.contains(&Event::FinesseFault));
+
+        // tucked under a roof, where no drop from the top could reach
+        engine.grid.tiles.clear();
+        for x in 0..GRID_WIDTH {
+            engine.grid.tiles.insert(IVec2::new(x, 6), GARBAGE_COLOR);
+        }
+        engine.active = Tetromino::new(TetrominoKind::T);
+        engine.active.position.y = 2;
+
+        let events = engine.apply(Input::HardDrop);
+        assert!(
+            events
+                .iter()
+                .any(|event| matches!(event, Event::PieceLocked(_)))
+        );
+        assert!(!events.contains(&Event::FinesseClean));
+        assert!(!events.contains(&Event::FinesseFault));
+        assert_eq!(engine.stats().finesse_faults, 0);
+    }
+}
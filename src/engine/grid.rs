@@ -0,0 +1,137 @@
+use catppuccin::ColorName;
+use glam::IVec2;
+
+use std::collections::HashMap;
+
+use super::piece::Tetromino;
+
+pub const GRID_HEIGHT: i32 = 22;
+pub const GRID_WIDTH: i32 = 10;
+
+/// The color of garbage rows, which belong to no piece.
+pub const GARBAGE_COLOR: ColorName = ColorName::Overlay2;
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct Grid {
+    pub tiles: HashMap<IVec2, ColorName>,
+}
+
+impl Grid {
+    /// Whether `tetromino` sits inside the walls and floor without
+    /// overlapping any locked tile.
+    pub fn fits(&self, tetromino: &Tetromino) -> bool {
+        !tetromino
+            .occupied_tiles()
+            .iter()
+            .any(|pos| self.tiles.contains_key(pos))
+            && !tetromino.is_in_ground()
+            && !tetromino.is_in_wall()
+    }
+
+    /// Whether `pos` is a locked tile or lies outside the walls and floor.
+    pub fn is_blocked(&self, pos: IVec2) -> bool {
+        pos.x < 0 || pos.x >= GRID_WIDTH || pos.y < 0 || self.tiles.contains_key(&pos)
+    }
+
+    /// Shifts every tile up a row and fills the bottom row with garbage,
+    /// leaving a hole in column `hole`.
+    pub fn push_garbage(&mut self, hole: i32) {
+        self.tiles = self
+            .tiles
+            .drain()
+            .map(|(pos, color)| (pos + IVec2::Y, color))
+            .collect();
+
+        for x in (0..GRID_WIDTH).filter(|&x| x != hole) {
+            self.tiles.insert(IVec2::new(x, 0), GARBAGE_COLOR);
+        }
+    }
+
+    /// Removes every full row, shifts the rows above it down and returns
+    /// the indices of the removed rows.
+    pub fn clear_lines(&mut self) -> Vec<i32> {
+        let full_rows = self.full_rows();
+        self.remove_rows(&full_rows);
+        full_rows
+    }
+
+    /// The indices of the rows with no gaps, bottom first.
+    pub fn full_rows(&self) -> Vec<i32> {
+        let mut row_counts = [0; GRID_HEIGHT as usize];
+
+        for pos in self.tiles.keys() {
+            if pos.y >= 0 && pos.y < GRID_HEIGHT {
+                row_counts[pos.y as usize] += 1;
+            }
+        }
+
+        let full_rows: Vec<i32> = row_counts
+            .iter()
+            .enumerate()
+            .filter_map(|(y, &count)| {
+                if count == GRID_WIDTH {
+                    Some(y as i32)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        full_rows
+    }
+
+    /// Removes the rows at `rows` and shifts the rows above them down.
+    pub fn remove_rows(&mut self, rows: &[i32]) {
+        if rows.is_empty() {
+            return;
+        }
+
+        for &y in rows {
+            self.tiles.retain(|pos, _| pos.y != y);
+        }
+
+        let mut new_tiles = HashMap::new();
+        for (pos, color) in self.tiles.drain() {
+            let shift = rows.iter().filter(|&&y| pos.y > y).count() as i32;
+            let new_pos = pos - IVec2::Y * shift;
+            new_tiles.insert(new_pos, color);
+        }
+        self.tiles = new_tiles;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut grid = Grid::default();
+        grid.tiles.insert(IVec2::new(3, 0), ColorName::Red);
+
+        grid.push_garbage(4);
+
+        assert_eq!(grid.tiles.get(&IVec2::new(3, 1)), Some(&ColorName::Red));
+        for x in 0..GRID_WIDTH {
+            let tile = grid.tiles.get(&IVec2::new(x, 0));
+            assert_eq!(tile, (x != 4).then_some(&GARBAGE_COLOR));
+        }
+        assert!(grid.full_rows().is_empty());
+    }
+
+    #[test]
+    fn removed_rows_drop_the_rows_above() {
+        let mut grid = Grid::default();
+        for x in 0..GRID_WIDTH {
+            grid.tiles.insert(IVec2::new(x, 0), GARBAGE_COLOR);
+            grid.tiles.insert(IVec2::new(x, 2), GARBAGE_COLOR);
+        }
+        grid.tiles.insert(IVec2::new(5, 1), ColorName::Red);
+        grid.tiles.insert(IVec2::new(5, 3), ColorName::Blue);
+
+        assert_eq!(grid.clear_lines(), [0, 2]);
+        assert_eq!(grid.tiles.len(), 2);
+        assert_eq!(grid.tiles.get(&IVec2::new(5, 0)), Some(&ColorName::Red));
+        assert_eq!(grid.tiles.get(&IVec2::new(5, 1)), Some(&ColorName::Blue));
+    }
+}
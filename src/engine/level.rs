@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+/// The fastest gravity, 20G: 20 rows per 60 Hz frame. Gravity is still
+/// applied row by row on each update, so a piece takes a couple of updates
+/// to fall the height of the board rather than appearing on the floor.
+const MAX_GRAVITY: Duration = Duration::from_nanos(1_000_000_000 / (20 * 60));
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Leveling {
+    pub start_level: u32,
+    /// Lines to clear before the level goes up.
+    pub lines_per_level: u32,
+}
+
+impl Default for Leveling {
+    fn default() -> Self {
+        Leveling {
+            start_level: 1,
+            lines_per_level: 10,
+        }
+    }
+}
+
+impl Leveling {
+    pub fn level(&self, lines: u32) -> u32 {
+        self.start_level + lines / self.lines_per_level.max(1)
+    }
+}
+
+/// How long a piece takes to fall one row at `level`, following the
+/// Guideline curve `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds.
+pub fn gravity(level: u32) -> Duration {
+    let level = level.max(1) as f64;
+    let seconds = (0.8 - (level - 1.0) * 0.007).max(0.0).powf(level - 1.0);
+
+    Duration::from_secs_f64(seconds).max(MAX_GRAVITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{Clear, Engine, Event, GARBAGE_COLOR, GRID_WIDTH, Held, Ruleset, Spin};
+    use crate::engine::{Input, Tetromino, TetrominoKind};
+
+    use glam::IVec2;
+
+    #[test]
+    fn follows_the_guideline_curve() {
+        assert_eq!(gravity(1), Duration::from_secs(1));
+        assert_eq!(gravity(2).as_millis(), 793);
+        assert_eq!(gravity(10).as_millis(), 64);
+
+        for level in 1..30 {
+            assert!(gravity(level + 1) <= gravity(level), "{level}");
+        }
+        assert_eq!(gravity(20), MAX_GRAVITY);
+        assert_eq!(gravity(100), MAX_GRAVITY);
+    }
+
+    #[test]
+    fn levels_up_every_few_lines() {
+        let leveling = Leveling {
+            start_level: 3,
+            lines_per_level: 5,
+        };
+
+        assert_eq!(leveling.level(0), 3);
+        assert_eq!(leveling.level(4), 3);
+        assert_eq!(leveling.level(5), 4);
+        assert_eq!(leveling.level(23), 7);
+    }
+
+    #[test]
+    fn max_gravity_reaches_the_floor_in_two_updates() {
+        let ruleset = Ruleset {
+            leveling: Leveling {
+                start_level: 20,
+                ..Leveling::default()
+            },
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, 1);
+        let tick = Duration::from_secs(1) / 64;
+
+        engine.update(tick, Held::default());
+        assert!(engine.active().position.y > engine.ghost().position.y);
+
+        engine.update(tick, Held::default());
+        assert_eq!(engine.active().position.y, engine.ghost().position.y);
+    }
+
+    #[test]
+    fn scores_and_levels_up_with_the_level() {
+        let ruleset = Ruleset {
+            leveling: Leveling {
+                start_level: 2,
+                lines_per_level: 1,
+            },
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, 1);
+        engine.active = Tetromino::new(TetrominoKind::I);
+        // the I lies flat across columns 4 to 7
+        for x in (0..GRID_WIDTH).filter(|x| !(4..=7).contains(x)) {
+            engine.grid.tiles.insert(IVec2::new(x, 0), GARBAGE_COLOR);
+        }
+        // left over, so the clear isn't a perfect clear
+        engine.grid.tiles.insert(IVec2::new(0, 1), GARBAGE_COLOR);
+
+        let events = engine.apply(Input::HardDrop);
+
+        assert!(events.contains(&Event::LevelUp(3)));
+        assert_eq!(engine.level(), 3);
+        // the clear scores at the level it was made on
+        assert_eq!(engine.score().0, 2 * Clear::new(1, Spin::None).points());
+    }
+}
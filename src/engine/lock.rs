@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+/// What restarts the lock timer of a grounded piece.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LockReset {
+    /// Any successful move or rotation, up to `max_resets` times per piece.
+    /// Falling below the lowest row reached so far restores the count.
+    #[default]
+    Move,
+    /// Only falling below the lowest row reached so far.
+    Step,
+    /// Nothing; each piece gets a single lock timer for its whole life.
+    Entry,
+}
+
+impl LockReset {
+    pub const ALL: [LockReset; 3] = [LockReset::Move, LockReset::Step, LockReset::Entry];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            LockReset::Move => "Move",
+            LockReset::Step => "Step",
+            LockReset::Entry => "Entry",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LockDelay {
+    pub duration: Duration,
+    pub max_resets: u32,
+    pub reset: LockReset,
+}
+
+impl Default for LockDelay {
+    fn default() -> Self {
+        LockDelay {
+            duration: Duration::from_millis(500),
+            max_resets: 15,
+            reset: LockReset::Move,
+        }
+    }
+}
+
+/// The lock timer of the active piece.
+pub(super) struct LockTimer {
+    elapsed: Duration,
+    resets: u32,
+    lowest_row: i32,
+}
+
+impl LockTimer {
+    pub(super) fn new(spawn_row: i32) -> Self {
+        LockTimer {
+            elapsed: Duration::ZERO,
+            resets: 0,
+            lowest_row: spawn_row,
+        }
+    }
+
+    /// Counts down while the piece rests on something. Returns whether the
+    /// piece should lock.
+    pub(super) fn tick(&mut self, delay: &LockDelay, delta: Duration) -> bool {
+        self.elapsed += delta;
+
+        self.elapsed >= delay.duration
+            || (delay.reset == LockReset::Move && self.resets >= delay.max_resets)
+    }
+
+    /// Called after the piece moved or rotated.
+    pub(super) fn on_move(&mut self, delay: &LockDelay) {
+        if delay.reset == LockReset::Move
+            && self.elapsed > Duration::ZERO
+            && self.resets < delay.max_resets
+        {
+            self.elapsed = Duration::ZERO;
+            self.resets += 1;
+        }
+    }
+
+    /// Called after the piece fell to `row`.
+    pub(super) fn on_fall(&mut self, delay: &LockDelay, row: i32) {
+        if row >= self.lowest_row {
+            return;
+        }
+
+        self.lowest_row = row;
+
+        match delay.reset {
+            LockReset::Move => {
+                self.elapsed = Duration::ZERO;
+                self.resets = 0;
+            }
+            LockReset::Step => self.elapsed = Duration::ZERO,
+            LockReset::Entry => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPAWN_ROW: i32 = 20;
+
+    fn delay(reset: LockReset) -> LockDelay {
+        LockDelay {
+            duration: Duration::from_millis(500),
+            max_resets: 2,
+            reset,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn locks_once_the_delay_is_up() {
+        for reset in LockReset::ALL {
+            let delay = delay(reset);
+            let mut timer = LockTimer::new(SPAWN_ROW);
+
+            assert!(!timer.tick(&delay, ms(499)), "{reset:?}");
+            assert!(timer.tick(&delay, ms(1)), "{reset:?}");
+        }
+    }
+
+    #[test]
+    fn move_reset_restarts_the_timer_until_the_cap() {
+        let delay = delay(LockReset::Move);
+        let mut timer = LockTimer::new(SPAWN_ROW);
+
+        // moving before the timer has started doesn't use up a reset
+        timer.on_move(&delay);
+        assert!(!timer.tick(&delay, ms(400)));
+
+        timer.on_move(&delay);
+        assert!(!timer.tick(&delay, ms(400)));
+
+        // the second reset uses up the cap, which locks on the next tick
+        timer.on_move(&delay);
+        assert!(timer.tick(&delay, ms(1)));
+
+        // further moves no longer restart the timer
+        timer.on_move(&delay);
+        assert!(timer.tick(&delay, ms(1)));
+    }
+
+    #[test]
+    fn move_reset_restores_the_cap_on_a_new_low() {
+        let delay = delay(LockReset::Move);
+        let mut timer = LockTimer::new(SPAWN_ROW);
+
+        for _ in 0..2 {
+            timer.tick(&delay, ms(100));
+            timer.on_move(&delay);
+        }
+        assert!(timer.tick(&delay, ms(1)));
+
+        // falling back to a row already reached restores nothing
+        timer.on_fall(&delay, SPAWN_ROW);
+        assert!(timer.tick(&delay, ms(1)));
+
+        timer.on_fall(&delay, SPAWN_ROW - 1);
+        assert!(!timer.tick(&delay, ms(499)));
+        timer.on_move(&delay);
+        assert!(!timer.tick(&delay, ms(499)));
+    }
+
+    #[test]
+    fn step_reset_only_restarts_on_a_new_low() {
+        let delay = delay(LockReset::Step);
+        let mut timer = LockTimer::new(SPAWN_ROW);
+
+        assert!(!timer.tick(&delay, ms(400)));
+        timer.on_move(&delay);
+        assert!(timer.tick(&delay, ms(100)));
+
+        timer.on_fall(&delay, SPAWN_ROW - 1);
+        assert!(!timer.tick(&delay, ms(499)));
+
+        // however many moves it makes, there is no cap to use up
+        for _ in 0..5 {
+            timer.on_move(&delay);
+        }
+        assert!(timer.tick(&delay, ms(1)));
+    }
+
+    #[test]
+    fn entry_reset_never_restarts() {
+        let delay = delay(LockReset::Entry);
+        let mut timer = LockTimer::new(SPAWN_ROW);
+
+        assert!(!timer.tick(&delay, ms(400)));
+        timer.on_move(&delay);
+        timer.on_fall(&delay, SPAWN_ROW - 5);
+        assert!(timer.tick(&delay, ms(100)));
+    }
+}
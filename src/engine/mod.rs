@@ -0,0 +1,1195 @@
+//! The game rules, free of any Bevy, window or input-device types.
+//!
+//! An [`Engine`] owns the board, the piece queue, the active piece and the
+//! score. A frontend feeds it abstract [`Input`]s and the passage of time and
+//! reacts to the [`Event`]s it returns.
+
+mod autoshift;
+mod delay;
+mod finesse;
+mod grid;
+mod level;
+mod lock;
+mod mode;
+mod piece;
+mod queue;
+mod randomizer;
+mod replay;
+mod scoring;
+mod stats;
+
+pub use autoshift::{Direction, Handling, Held};
+pub use delay::Delays;
+pub use finesse::{FinesseFault, Move, optimal_moves, placements};
+pub use grid::{GARBAGE_COLOR, GRID_HEIGHT, GRID_WIDTH, Grid};
+pub use level::{Leveling, gravity};
+pub use lock::{LockDelay, LockReset};
+pub use mode::GameMode;
+pub use piece::{
+    Rotation, TETROMINO_SHAPES, TETROMINOS, Tetromino, TetrominoKind, get_wall_kick_offsets,
+};
+pub use queue::PieceQueue;
+pub use randomizer::{Randomizer, RandomizerKind};
+pub use replay::{Playback, REPLAY_VERSION, Record, Replay, ReplayError};
+pub use scoring::{Clear, Spin, combo_points, detect_spin, perfect_clear_points};
+pub use stats::Stats;
+
+use autoshift::AutoShift;
+use catppuccin::ColorName;
+use delay::Wait;
+use glam::IVec2;
+use lock::LockTimer;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use std::time::Duration;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Score(pub u32);
+
+/// Everything about a game that is fixed when it starts.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ruleset {
+    pub mode: GameMode,
+    pub randomizer: RandomizerKind,
+    pub lock_delay: LockDelay,
+    pub delays: Delays,
+    pub leveling: Leveling,
+    /// Ends the game when any part of a piece locks above the skyline,
+    /// rather than only when all of it does.
+    pub partial_lock_out: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateLeft,
+    RotateRight,
+    Rotate180,
+    Hold,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    PieceLocked(Lock),
+    PieceHeld,
+    /// The active piece was hard dropped by this many rows.
+    HardDropped(u32),
+    /// A lock that cleared lines or was a T-spin.
+    Cleared(Clear),
+    /// The clear continued a chain of difficult clears and scored half as
+    /// much again.
+    BackToBack(Clear),
+    /// This many line-clearing locks in a row after the first.
+    Combo(u32),
+    /// The clear left the board empty.
+    PerfectClear,
+    /// The piece was placed with more moves and rotations than it needed,
+    /// as told by [`Engine::finesse_fault`].
+    FinesseFault,
+    /// The piece was placed with no more moves and rotations than it
+    /// needed. Tucks, spins and anything else finesse can't judge get
+    /// neither this nor [`Event::FinesseFault`].
+    FinesseClean,
+    /// A training game sent the piece back to the top after a finesse
+    /// fault, rather than locking it.
+    PieceRestarted,
+    LevelUp(u32),
+    /// Another [`SPLIT_LINES`] lines were cleared at this time.
+    Split(Duration),
+    GameOver(End),
+}
+
+/// The piece behind an [`Event::PieceLocked`], for frontends to show more
+/// than the board does.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Lock {
+    /// The piece where it locked.
+    pub piece: Tetromino,
+    /// The tiles of the rows it filled, as they were before being cleared.
+    pub cleared: Vec<(IVec2, ColorName)>,
+}
+
+/// How often a split time is taken, in lines.
+pub const SPLIT_LINES: u32 = 10;
+
+/// Why a game ended.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum End {
+    TopOut(TopOut),
+    /// The mode's goal was reached, such as the lines of a sprint.
+    Completed,
+    /// A timed mode ran out of time.
+    TimeUp,
+}
+
+impl End {
+    pub const fn name(self) -> &'static str {
+        match self {
+            End::TopOut(reason) => reason.name(),
+            End::Completed => "Completed",
+            End::TimeUp => "Time up",
+        }
+    }
+}
+
+/// How the stack overflowed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TopOut {
+    /// A new piece spawned overlapping the stack.
+    BlockOut,
+    /// A piece locked entirely above the skyline.
+    LockOut,
+    /// A piece locked partly above the skyline, with
+    /// [`Ruleset::partial_lock_out`] enabled.
+    PartialLockOut,
+}
+
+impl TopOut {
+    pub const fn name(self) -> &'static str {
+        match self {
+            TopOut::BlockOut => "Block out",
+            TopOut::LockOut => "Lock out",
+            TopOut::PartialLockOut => "Partial lock out",
+        }
+    }
+}
+
+pub struct Engine {
+    ruleset: Ruleset,
+    handling: Handling,
+    autoshift: AutoShift,
+    grid: Grid,
+    queue: PieceQueue,
+    active: Tetromino,
+    hold: Option<TetrominoKind>,
+    /// Set once the active piece has been swapped into the hold slot, so it
+    /// cannot be swapped again until it locks.
+    hold_used: bool,
+    score: Score,
+    lines: u32,
+    level: u32,
+    /// Drives the randomizer and garbage, so the same seed and inputs
+    /// always play out the same game.
+    seed: u64,
+    rng: StdRng,
+    gravity_elapsed: Duration,
+    lock_timer: LockTimer,
+    /// The kick test used by the last successful action if it was a
+    /// rotation, for T-spin detection.
+    last_kick: Option<usize>,
+    /// Consecutive line-clearing locks after the first, or `None` once a
+    /// lock clears nothing.
+    combo: Option<u32>,
+    /// Set while the last line clear was a difficult one.
+    back_to_back: bool,
+    /// Time played, which stops once the game ends.
+    time: Duration,
+    pieces: u32,
+    /// Moves and rotations of the active piece since it spawned, for
+    /// finesse.
+    piece_moves: u32,
+    finesse_fault: Option<FinesseFault>,
+    stats: Stats,
+    splits: Vec<Duration>,
+    /// Garbage rows left at the bottom of the board.
+    garbage: u32,
+    /// Set while the next piece waits out a line clear or entry delay.
+    wait: Option<Wait>,
+    end: Option<End>,
+    events: Vec<Event>,
+}
+
+impl Engine {
+    pub fn new(ruleset: Ruleset, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut queue = PieceQueue::new(ruleset.randomizer, &mut rng);
+        let active = Tetromino::new(queue.next(&mut rng));
+        let lock_timer = LockTimer::new(active.position.y);
+
+        let mut engine = Engine {
+            ruleset,
+            handling: Handling::default(),
+            autoshift: AutoShift::default(),
+            grid: Grid::default(),
+            queue,
+            active,
+            hold: None,
+            hold_used: false,
+            score: Score::default(),
+            lines: 0,
+            level: ruleset.leveling.start_level,
+            seed,
+            rng,
+            gravity_elapsed: Duration::ZERO,
+            lock_timer,
+            last_kick: None,
+            combo: None,
+            back_to_back: false,
+            time: Duration::ZERO,
+            pieces: 0,
+            piece_moves: 0,
+            finesse_fault: None,
+            stats: Stats::default(),
+            splits: Vec::new(),
+            garbage: 0,
+            wait: None,
+            end: None,
+            events: Vec::new(),
+        };
+
+        engine.add_garbage(ruleset.mode.garbage_rows());
+        engine
+    }
+
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn handling(&self) -> &Handling {
+        &self.handling
+    }
+
+    /// Handling is a player preference rather than a rule, so it may change
+    /// mid-game.
+    pub fn set_handling(&mut self, handling: Handling) {
+        self.handling = handling;
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn queue(&self) -> &PieceQueue {
+        &self.queue
+    }
+
+    pub fn active(&self) -> &Tetromino {
+        &self.active
+    }
+
+    pub fn hold(&self) -> Option<TetrominoKind> {
+        self.hold
+    }
+
+    pub fn can_hold(&self) -> bool {
+        !self.hold_used
+    }
+
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// How many pieces have locked.
+    pub fn pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    /// The most recent finesse fault, if there has been one.
+    pub fn finesse_fault(&self) -> Option<&FinesseFault> {
+        self.finesse_fault.as_ref()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn pieces_per_second(&self) -> f32 {
+        if self.time.is_zero() {
+            0.0
+        } else {
+            self.pieces as f32 / self.time.as_secs_f32()
+        }
+    }
+
+    /// Inputs applied a minute, counted the same way as
+    /// [`Stats::inputs`].
+    pub fn actions_per_minute(&self) -> f32 {
+        if self.time.is_zero() {
+            0.0
+        } else {
+            self.stats.inputs as f32 * 60.0 / self.time.as_secs_f32()
+        }
+    }
+
+    pub fn inputs_per_piece(&self) -> f32 {
+        if self.pieces == 0 {
+            0.0
+        } else {
+            self.stats.inputs as f32 / self.pieces as f32
+        }
+    }
+
+    /// The time at which every [`SPLIT_LINES`] lines were cleared.
+    pub fn splits(&self) -> &[Duration] {
+        &self.splits
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// What ended the game, if it is over.
+    pub fn end(&self) -> Option<End> {
+        self.end
+    }
+
+    /// Whether the game is waiting out a delay between pieces, with no
+    /// piece in play.
+    pub fn is_between_pieces(&self) -> bool {
+        self.wait.is_some()
+    }
+
+    /// The full rows waiting out the line clear delay, and how far through
+    /// the delay the game is, from 0 to 1.
+    pub fn clearing(&self) -> Option<(&[i32], f32)> {
+        match &self.wait {
+            Some(Wait::LineClear { rows, elapsed }) => Some((
+                rows,
+                elapsed.as_secs_f32() / self.ruleset.delays.line_clear.as_secs_f32(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Where the active piece would land if it dropped straight down.
+    pub fn ghost(&self) -> Tetromino {
+        let mut ghost = self.active.clone();
+
+        while !ghost
+            .occupied_tiles()
+            .iter()
+            .any(|tile| self.grid.tiles.contains_key(tile))
+            && !ghost.is_in_ground()
+        {
+            ghost.move_down();
+        }
+
+        ghost.move_up();
+
+        ghost
+    }
+
+    /// Applies a player input to the active piece. Inputs that would move the
+    /// piece into a wall, the floor or the stack are ignored.
+    pub fn apply(&mut self, input: Input) -> Vec<Event> {
+        if self.is_game_over() {
+            return Vec::new();
+        }
+
+        self.stats.inputs += 1;
+
+        if self.wait.is_some() {
+            // a direction pressed between pieces starts charging auto shift
+            match input {
+                Input::MoveLeft => self.autoshift.press(Direction::Left),
+                Input::MoveRight => self.autoshift.press(Direction::Right),
+                _ => {}
+            }
+            return Vec::new();
+        }
+
+        if matches!(
+            input,
+            Input::MoveLeft
+                | Input::MoveRight
+                | Input::RotateLeft
+                | Input::RotateRight
+                | Input::Rotate180
+        ) {
+            self.piece_moves += 1;
+        }
+
+        match input {
+            Input::MoveLeft => self.press(Direction::Left),
+            Input::MoveRight => self.press(Direction::Right),
+            Input::SoftDrop => self.try_fall(),
+            Input::HardDrop => self.hard_drop(),
+            Input::RotateLeft => self.try_rotate(Tetromino::rotate_left),
+            Input::RotateRight => self.try_rotate(Tetromino::rotate_right),
+            Input::Rotate180 => self.try_rotate(Tetromino::rotate_180),
+            Input::Hold => self.swap_hold(),
+        };
+
+        std::mem::take(&mut self.events)
+    }
+
+    /// Advances the game by `delta` with the `held` inputs kept down. The
+    /// active piece falls at the current level's [`gravity`], or [`Handling`]'s
+    /// soft drop factor times as fast while soft dropping, and locks once it
+    /// has rested on the stack for the ruleset's lock delay.
+    pub fn update(&mut self, delta: Duration, held: Held) -> Vec<Event> {
+        if self.is_game_over() {
+            return Vec::new();
+        }
+
+        self.time += delta;
+
+        if let Some(limit) = self.ruleset.mode.time_limit()
+            && self.time >= limit
+        {
+            self.time = limit;
+            self.finish(End::TimeUp);
+            return std::mem::take(&mut self.events);
+        }
+
+        if self.wait.is_some() {
+            // held directions keep charging for the next piece
+            self.autoshift.tick(&self.handling, held, delta);
+            self.advance_wait(delta);
+            return std::mem::take(&mut self.events);
+        }
+
+        if let Some((direction, shifts)) = self.autoshift.tick(&self.handling, held, delta) {
+            self.shift(direction, shifts);
+        }
+
+        let interval = if held.soft_drop {
+            gravity(self.level) / self.handling.soft_drop_factor.max(1)
+        } else {
+            gravity(self.level)
+        };
+
+        self.gravity_elapsed += delta;
+        while self.gravity_elapsed >= interval {
+            self.gravity_elapsed -= interval;
+
+            if !self.try_fall() {
+                self.gravity_elapsed = Duration::ZERO;
+                break;
+            }
+        }
+
+        if self.is_grounded() && self.lock_timer.tick(&self.ruleset.lock_delay, delta) {
+            self.lock();
+        }
+
+        std::mem::take(&mut self.events)
+    }
+
+    fn is_grounded(&self) -> bool {
+        let mut below = self.active.clone();
+        below.move_down();
+
+        !self.grid.fits(&below)
+    }
+
+    fn try_move(&mut self, f: impl FnOnce(&mut Tetromino)) -> bool {
+        let mut new_tetromino = self.active.clone();
+        f(&mut new_tetromino);
+
+        if self.grid.fits(&new_tetromino) {
+            self.active = new_tetromino;
+            self.last_kick = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn try_shift(&mut self, f: impl FnOnce(&mut Tetromino)) -> bool {
+        let moved = self.try_move(f);
+        if moved {
+            self.lock_timer.on_move(&self.ruleset.lock_delay);
+        }
+
+        moved
+    }
+
+    fn press(&mut self, direction: Direction) -> bool {
+        self.autoshift.press(direction);
+        self.shift(direction, 1)
+    }
+
+    /// Shifts the active piece up to `cells` columns, stopping at the first
+    /// obstacle.
+    fn shift(&mut self, direction: Direction, cells: u32) -> bool {
+        let step = match direction {
+            Direction::Left => Tetromino::move_left,
+            Direction::Right => Tetromino::move_right,
+        };
+
+        let mut shifted = false;
+        for _ in 0..cells {
+            if !self.try_shift(step) {
+                break;
+            }
+            shifted = true;
+        }
+
+        shifted
+    }
+
+    fn try_fall(&mut self) -> bool {
+        let fell = self.try_move(Tetromino::move_down);
+        if fell {
+            self.lock_timer
+                .on_fall(&self.ruleset.lock_delay, self.active.position.y);
+        }
+
+        fell
+    }
+
+    fn try_rotate(&mut self, f: impl FnOnce(&mut Tetromino)) -> bool {
+        let mut new_tetromino = self.active.clone();
+        f(&mut new_tetromino);
+
+        let offsets = get_wall_kick_offsets(
+            self.active.kind,
+            self.active.rotation,
+            new_tetromino.rotation,
+        );
+
+        for (kick, offset) in offsets.into_iter().enumerate() {
+            new_tetromino.position += offset;
+
+            if self.grid.fits(&new_tetromino) {
+                self.active = new_tetromino;
+                self.last_kick = Some(kick);
+                self.lock_timer.on_move(&self.ruleset.lock_delay);
+                return true;
+            } else {
+                new_tetromino.position -= offset
+            }
+        }
+
+        false
+    }
+
+    /// Drops the active piece onto the stack and locks it straight away.
+    fn hard_drop(&mut self) -> bool {
+        let ghost = self.ghost();
+        let distance = self.active.position.y - ghost.position.y;
+
+        self.active = ghost;
+        if distance > 0 {
+            self.last_kick = None;
+        }
+        self.events.push(Event::HardDropped(distance as u32));
+        self.lock();
+
+        true
+    }
+
+    /// Swaps the active piece with the held one, or with the next piece if
+    /// nothing is held yet. The incoming piece starts over from the spawn
+    /// position.
+    fn swap_hold(&mut self) -> bool {
+        if self.hold_used {
+            return false;
+        }
+
+        let kind = match self.hold.replace(self.active.kind) {
+            Some(held) => held,
+            None => self.queue.next(&mut self.rng),
+        };
+
+        self.spawn(kind);
+        self.hold_used = true;
+        self.events.push(Event::PieceHeld);
+
+        true
+    }
+
+    fn spawn(&mut self, kind: TetrominoKind) {
+        self.active = Tetromino::new(kind);
+        self.gravity_elapsed = Duration::ZERO;
+        self.lock_timer = LockTimer::new(self.active.position.y);
+        self.last_kick = None;
+        self.piece_moves = 0;
+        self.autoshift.cut(&self.handling);
+
+        if !self.grid.fits(&self.active) {
+            self.finish(End::TopOut(TopOut::BlockOut));
+        }
+    }
+
+    fn finish(&mut self, end: End) {
+        if self.end.is_none() {
+            self.end = Some(end);
+            self.events.push(Event::GameOver(end));
+        }
+    }
+
+    /// Whether the active piece could have fallen straight down from the
+    /// top of the board to where it is, rather than being tucked or spun in.
+    fn is_dropped(&self) -> bool {
+        let mut above = self.active.clone();
+
+        while above.position.y < GRID_HEIGHT {
+            above.move_up();
+            if !self.grid.fits(&above) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Counts a finesse fault if the active piece was dropped with more
+    /// moves and rotations than it needed, returning whether it was. A
+    /// piece that was judged and needed no more gets [`Event::FinesseClean`].
+    fn check_finesse(&mut self) -> bool {
+        if !self.is_dropped() {
+            return false;
+        }
+
+        let Some(optimal) = optimal_moves(&self.active) else {
+            return false;
+        };
+
+        if self.piece_moves <= optimal.len() as u32 {
+            self.events.push(Event::FinesseClean);
+            return false;
+        }
+
+        self.stats.finesse_faults += 1;
+        self.finesse_fault = Some(FinesseFault {
+            used: self.piece_moves,
+            optimal,
+        });
+        self.events.push(Event::FinesseFault);
+
+        true
+    }
+
+    /// Writes the active piece into the grid, clears any full rows and
+    /// spawns the next piece.
+    fn lock(&mut self) {
+        if self.check_finesse() && self.ruleset.mode == (GameMode::Training { restart: true }) {
+            self.spawn(self.active.kind);
+            self.events.push(Event::PieceRestarted);
+            return;
+        }
+
+        let color = self.active.color();
+        let spin = detect_spin(&self.grid, &self.active, self.last_kick);
+        let tiles = self.active.occupied_tiles();
+
+        let above_skyline = tiles.iter().filter(|pos| pos.y >= GRID_HEIGHT).count();
+        let lock_out = if above_skyline == tiles.len() {
+            Some(TopOut::LockOut)
+        } else if above_skyline > 0 && self.ruleset.partial_lock_out {
+            Some(TopOut::PartialLockOut)
+        } else {
+            None
+        };
+
+        for pos in tiles {
+            self.grid.tiles.insert(pos, color);
+        }
+
+        let full_rows = self.grid.full_rows();
+
+        self.pieces += 1;
+        self.events.push(Event::PieceLocked(Lock {
+            piece: self.active.clone(),
+            cleared: self
+                .grid
+                .tiles
+                .iter()
+                .filter(|(pos, _)| full_rows.contains(&pos.y))
+                .map(|(&pos, &color)| (pos, color))
+                .collect(),
+        }));
+
+        self.score_clear(&full_rows, spin);
+
+        let delay = self.ruleset.delays.line_clear;
+        if full_rows.is_empty() || delay.is_zero() || lock_out.is_some() || self.is_game_over() {
+            self.remove_rows(&full_rows);
+        } else {
+            self.wait = Some(Wait::LineClear {
+                rows: full_rows,
+                elapsed: Duration::ZERO,
+            });
+        }
+
+        if let Some(reason) = lock_out {
+            self.finish(End::TopOut(reason));
+        }
+
+        if self.wait.is_none() {
+            self.enter();
+        }
+    }
+
+    /// Spawns the next piece, once the entry delay is up if there is one.
+    fn enter(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+
+        if self.ruleset.delays.entry.is_zero() {
+            self.spawn_next();
+        } else {
+            self.wait = Some(Wait::Entry {
+                elapsed: Duration::ZERO,
+            });
+        }
+    }
+
+    fn spawn_next(&mut self) {
+        let kind = self.queue.next(&mut self.rng);
+        self.spawn(kind);
+        self.hold_used = false;
+    }
+
+    /// Counts `delta` towards the delay being waited out, and carries on
+    /// with the game once it is up.
+    fn advance_wait(&mut self, delta: Duration) {
+        let delays = self.ruleset.delays;
+
+        match self.wait.take() {
+            Some(Wait::LineClear { rows, elapsed }) => {
+                let elapsed = elapsed + delta;
+                if elapsed < delays.line_clear {
+                    self.wait = Some(Wait::LineClear { rows, elapsed });
+                } else {
+                    self.remove_rows(&rows);
+                    self.enter();
+                }
+            }
+            Some(Wait::Entry { elapsed }) => {
+                let elapsed = elapsed + delta;
+                if elapsed < delays.entry {
+                    self.wait = Some(Wait::Entry { elapsed });
+                } else {
+                    self.spawn_next();
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Takes the full `rows` off the board, along with any garbage among
+    /// them.
+    fn remove_rows(&mut self, rows: &[i32]) {
+        self.grid.remove_rows(rows);
+
+        // garbage only ever fills the bottom rows
+        let garbage_cleared = rows.iter().filter(|&&y| y < self.garbage as i32).count();
+        self.garbage -= garbage_cleared as u32;
+
+        self.dig();
+    }
+
+    /// Pushes `rows` rows of garbage up from the bottom, each with a hole in
+    /// a random column.
+    fn add_garbage(&mut self, rows: u32) {
+        for _ in 0..rows {
+            let hole = self.rng.random_range(0..GRID_WIDTH);
+            self.grid.push_garbage(hole);
+        }
+
+        self.garbage += rows;
+    }
+
+    /// Completes a dig once its garbage is gone, or tops an infinite dig
+    /// back up to its starting rows.
+    fn dig(&mut self) {
+        let GameMode::Dig { rows, infinite } = self.ruleset.mode else {
+            return;
+        };
+
+        if infinite {
+            self.add_garbage(rows.saturating_sub(self.garbage));
+        } else if self.garbage == 0 {
+            self.finish(End::Completed);
+        }
+    }
+
+    /// Scores the lock that filled `full_rows`, before they are removed.
+    fn score_clear(&mut self, full_rows: &[i32], spin: Spin) {
+        let clear = Clear {
+            lines: full_rows.len() as u32,
+            spin,
+        };
+
+        if clear.lines == 0 {
+            self.combo = None;
+        }
+
+        if clear == Clear::default() {
+            return;
+        }
+
+        let mut points = clear.points();
+        self.stats.count_clear(clear);
+        self.events.push(Event::Cleared(clear));
+
+        // a T-spin that clears nothing neither continues nor breaks the chain
+        if clear.lines > 0 {
+            let back_to_back = self.back_to_back && clear.is_difficult();
+            self.back_to_back = clear.is_difficult();
+
+            if back_to_back {
+                points += points / 2;
+                self.events.push(Event::BackToBack(clear));
+            }
+
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            self.combo = Some(combo);
+            self.stats.longest_combo = self.stats.longest_combo.max(combo);
+            if combo > 0 {
+                points += combo_points(combo);
+                self.events.push(Event::Combo(combo));
+            }
+
+            // nothing is left once the full rows are removed
+            if self.grid.tiles.len() == full_rows.len() * GRID_WIDTH as usize {
+                points += perfect_clear_points(clear.lines, back_to_back);
+                self.events.push(Event::PerfectClear);
+            }
+        }
+
+        self.score.0 += points * self.level;
+
+        let splits_before = self.lines / SPLIT_LINES;
+        self.lines += clear.lines;
+        for _ in splits_before..self.lines / SPLIT_LINES {
+            self.splits.push(self.time);
+            self.events.push(Event::Split(self.time));
+        }
+
+        if let Some(goal) = self.ruleset.mode.goal_lines()
+            && self.lines >= goal
+        {
+            self.finish(End::Completed);
+        }
+
+        let level = self.ruleset.leveling.level(self.lines);
+        if level > self.level {
+            self.level = level;
+            self.events.push(Event::LevelUp(level));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An engine whose active piece is a freshly spawned `kind`.
+    fn engine_with(kind: TetrominoKind) -> Engine {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.active = Tetromino::new(kind);
+        engine
+    }
+
+    /// Fills row `y` apart from the columns in `gaps`.
+    fn fill_row(engine: &mut Engine, y: i32, gaps: std::ops::RangeInclusive<i32>) {
+        for x in (0..GRID_WIDTH).filter(|x| !gaps.contains(x)) {
+            engine.grid.tiles.insert(IVec2::new(x, y), GARBAGE_COLOR);
+        }
+    }
+
+    #[test]
+    fn spawns_the_first_piece_at_the_top() {
+        let engine = Engine::new(Ruleset::default(), 1);
+        let spawned = Tetromino::new(engine.active().kind);
+
+        assert_eq!(engine.active().position, spawned.position);
+        assert_eq!(engine.active().rotation, Rotation::North);
+        assert_eq!(engine.pieces(), 0);
+        assert!(engine.grid().tiles.is_empty());
+        assert!(!engine.is_game_over());
+    }
+
+    #[test]
+    fn moves_and_rotates_within_the_walls() {
+        let mut engine = engine_with(TetrominoKind::T);
+        let x = engine.active().position.x;
+
+        engine.apply(Input::MoveLeft);
+        assert_eq!(engine.active().position.x, x - 1);
+        engine.apply(Input::MoveRight);
+        engine.apply(Input::MoveRight);
+        assert_eq!(engine.active().position.x, x + 1);
+
+        engine.apply(Input::RotateRight);
+        assert_eq!(engine.active().rotation, Rotation::East);
+        engine.apply(Input::RotateLeft);
+        engine.apply(Input::RotateLeft);
+        assert_eq!(engine.active().rotation, Rotation::West);
+        engine.apply(Input::Rotate180);
+        assert_eq!(engine.active().rotation, Rotation::East);
+
+        // moves into the wall are ignored
+        for _ in 0..GRID_WIDTH {
+            engine.apply(Input::MoveLeft);
+        }
+        assert!(!engine.active().is_in_wall());
+        assert_eq!(
+            engine
+                .active()
+                .occupied_tiles()
+                .map(|tile| tile.x)
+                .iter()
+                .min(),
+            Some(&0)
+        );
+    }
+
+    #[test]
+    fn hard_drop_locks_onto_the_floor() {
+        let mut engine = engine_with(TetrominoKind::T);
+        let next = engine.queue().upcoming[0];
+        let spawn_row = engine.active().position.y;
+        let landed = engine.ghost();
+
+        let events = engine.apply(Input::HardDrop);
+
+        assert_eq!(
+            events[..3],
+            [
+                Event::HardDropped(spawn_row as u32),
+                Event::FinesseClean,
+                Event::PieceLocked(Lock {
+                    piece: landed,
+                    cleared: Vec::new(),
+                }),
+            ]
+        );
+        assert_eq!(engine.pieces(), 1);
+        assert_eq!(engine.grid().tiles.len(), 4);
+        assert!(
+            engine
+                .grid()
+                .tiles
+                .keys()
+                .all(|tile| tile.y == 0 || *tile == IVec2::new(5, 1))
+        );
+        assert_eq!(engine.active().kind, next);
+        assert_eq!(engine.active().position.y, spawn_row);
+    }
+
+    #[test]
+    fn gravity_and_the_lock_delay_lock_a_piece() {
+        let mut engine = engine_with(TetrominoKind::O);
+
+        let mut events = Vec::new();
+        for _ in 0..10_000 {
+            events.extend(engine.update(Duration::from_millis(10), Held::default()));
+            if engine.pieces() > 0 {
+                break;
+            }
+        }
+
+        assert!(
+            events
+                .iter()
+                .any(|event| matches!(event, Event::PieceLocked(_)))
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, Event::HardDropped(_)))
+        );
+        assert_eq!(engine.grid().tiles.len(), 4);
+    }
+
+    #[test]
+    fn clears_full_rows_and_drops_the_rest() {
+        let mut engine = engine_with(TetrominoKind::I);
+        // the I lies flat across columns 4 to 7
+        fill_row(&mut engine, 0, 4..=7);
+        engine.grid.tiles.insert(IVec2::new(0, 1), ColorName::Red);
+
+        let events = engine.apply(Input::HardDrop);
+
+        assert!(events.contains(&Event::Cleared(Clear::new(1, Spin::None))));
+        // the lock keeps the row as it was before it was cleared
+        let cleared = events.iter().find_map(|event| match event {
+            Event::PieceLocked(lock) => Some(lock.cleared.len()),
+            _ => None,
+        });
+        assert_eq!(cleared, Some(GRID_WIDTH as usize));
+        assert_eq!(engine.lines(), 1);
+        assert_eq!(engine.score(), Score(100));
+        assert_eq!(
+            engine.grid().tiles,
+            [(IVec2::new(0, 0), ColorName::Red)].into()
+        );
+    }
+
+    #[test]
+    fn tops_out_once_the_stack_reaches_the_spawn() {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+
+        let mut events = Vec::new();
+        while !engine.is_game_over() && engine.pieces() < 100 {
+            events.extend(engine.apply(Input::HardDrop));
+        }
+
+        assert!(matches!(engine.end(), Some(End::TopOut(_))));
+        assert!(matches!(
+            events.last(),
+            Some(Event::GameOver(End::TopOut(_)))
+        ));
+        assert!(engine.apply(Input::HardDrop).is_empty());
+        assert!(
+            engine
+                .update(Duration::from_secs(1), Held::default())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn blocks_out_when_a_piece_spawns_into_the_stack() {
+        let mut engine = engine_with(TetrominoKind::T);
+        let spawn = Tetromino::new(TetrominoKind::T).position;
+        engine.grid.tiles.insert(spawn, ColorName::Red);
+
+        engine.spawn(TetrominoKind::T);
+
+        assert_eq!(engine.end(), Some(End::TopOut(TopOut::BlockOut)));
+        assert_eq!(
+            engine.events,
+            [Event::GameOver(End::TopOut(TopOut::BlockOut))]
+        );
+    }
+
+    #[test]
+    fn locks_out_when_a_piece_locks_above_the_skyline() {
+        let mut engine = engine_with(TetrominoKind::T);
+        // the T spawns on the skyline, so this holds it up entirely above
+        fill_row(&mut engine, GRID_HEIGHT - 1, 7..=9);
+
+        let events = engine.apply(Input::HardDrop);
+
+        assert!(events.contains(&Event::HardDropped(0)));
+        assert_eq!(engine.end(), Some(End::TopOut(TopOut::LockOut)));
+    }
+
+    #[test]
+    fn partial_lock_out_is_optional() {
+        for partial_lock_out in [false, true] {
+            let mut engine = Engine::new(
+                Ruleset {
+                    partial_lock_out,
+                    ..Ruleset::default()
+                },
+                1,
+            );
+            // a T to the left rests on the top row, with its point above it
+            engine.active = Tetromino::new(TetrominoKind::T);
+            engine.active.position.x = 2;
+            fill_row(&mut engine, GRID_HEIGHT - 2, 4..=9);
+
+            engine.apply(Input::HardDrop);
+
+            let end = partial_lock_out.then_some(End::TopOut(TopOut::PartialLockOut));
+            assert_eq!(engine.end(), end);
+            assert_eq!(engine.pieces(), 1);
+        }
+    }
+
+    #[test]
+    fn kicks_off_the_wall() {
+        let mut engine = engine_with(TetrominoKind::I);
+        engine.apply(Input::RotateRight);
+        for _ in 0..GRID_WIDTH {
+            engine.apply(Input::MoveRight);
+        }
+        assert_eq!(engine.active().position.x, GRID_WIDTH - 2);
+
+        // lying flat in place would poke through the wall, so it kicks left
+        engine.apply(Input::RotateLeft);
+        assert_eq!(engine.active().rotation, Rotation::North);
+        assert_eq!(engine.active().position.x, GRID_WIDTH - 3);
+        assert_eq!(engine.last_kick, Some(2));
+    }
+
+    #[test]
+    fn holds_once_per_piece() {
+        let mut engine = engine_with(TetrominoKind::T);
+        let next = engine.queue().upcoming[0];
+        engine.apply(Input::MoveLeft);
+
+        assert_eq!(engine.apply(Input::Hold), [Event::PieceHeld]);
+        assert_eq!(engine.hold(), Some(TetrominoKind::T));
+        assert_eq!(engine.active().kind, next);
+        assert!(!engine.can_hold());
+
+        // a second swap has to wait for the next piece
+        assert!(engine.apply(Input::Hold).is_empty());
+        assert_eq!(engine.active().kind, next);
+
+        engine.apply(Input::HardDrop);
+        assert!(engine.can_hold());
+        engine.apply(Input::Hold);
+
+        // the held piece comes back from the spawn position
+        let spawned = Tetromino::new(TetrominoKind::T);
+        assert_eq!(engine.active().kind, TetrominoKind::T);
+        assert_eq!(engine.active().position, spawned.position);
+    }
+
+    /// The pieces a dig seeded with `seed` deals, and the garbage it
+    /// starts with.
+    fn deal(seed: u64) -> (Vec<TetrominoKind>, Grid) {
+        let ruleset = Ruleset {
+            mode: GameMode::Dig {
+                rows: 10,
+                infinite: true,
+            },
+            ..Ruleset::default()
+        };
+        let mut engine = Engine::new(ruleset, seed);
+        let grid = engine.grid().clone();
+
+        let mut kinds = vec![engine.active().kind];
+        kinds.extend(engine.queue().upcoming.iter().copied());
+        for _ in 0..14 {
+            kinds.push(engine.queue.next(&mut engine.rng));
+        }
+
+        (kinds, grid)
+    }
+
+    #[test]
+    fn the_seed_decides_the_pieces_and_garbage() {
+        let engine = Engine::new(Ruleset::default(), 7);
+        assert_eq!(engine.seed(), 7);
+
+        assert_eq!(deal(7), deal(7));
+
+        let (kinds, grid) = deal(7);
+        let (other_kinds, other_grid) = deal(8);
+        assert_ne!(kinds, other_kinds);
+        assert_ne!(grid, other_grid);
+    }
+
+    #[test]
+    fn counts_actions_per_minute() {
+        let mut engine = engine_with(TetrominoKind::T);
+        assert_eq!(engine.actions_per_minute(), 0.0);
+
+        engine.apply(Input::MoveLeft);
+        engine.apply(Input::RotateRight);
+        engine.apply(Input::HardDrop);
+        engine.update(Duration::from_secs(30), Held::default());
+
+        assert_eq!(engine.actions_per_minute(), 6.0);
+    }
+}
@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+/// What a game is played for.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum GameMode {
+    /// Endless play for score, with the level rising as lines are cleared.
+    #[default]
+    Marathon,
+    /// A race to clear this many lines.
+    Sprint { lines: u32 },
+    /// Score as much as possible before time runs out.
+    Ultra { duration: Duration },
+    /// Clear this many rows of garbage, or with `infinite` keep digging as
+    /// new rows are pushed up to replace the cleared ones.
+    Dig { rows: u32, infinite: bool },
+    /// Endless finesse practice, where every piece placed with more moves
+    /// and rotations than it needed is flagged. With `restart` such a
+    /// piece goes back to the top to be placed again.
+    Training { restart: bool },
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 6] = [
+        GameMode::Marathon,
+        GameMode::Sprint { lines: 40 },
+        GameMode::Ultra {
+            duration: Duration::from_secs(120),
+        },
+        GameMode::Dig {
+            rows: 10,
+            infinite: false,
+        },
+        GameMode::Dig {
+            rows: 10,
+            infinite: true,
+        },
+        GameMode::Training { restart: false },
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint { .. } => "Sprint",
+            GameMode::Ultra { .. } => "Ultra",
+            GameMode::Dig {
+                infinite: false, ..
+            } => "Dig",
+            GameMode::Dig { infinite: true, .. } => "Infinite Dig",
+            GameMode::Training { .. } => "Training",
+        }
+    }
+
+    /// The lines that complete the game, if it has a line goal.
+    pub const fn goal_lines(self) -> Option<u32> {
+        match self {
+            GameMode::Sprint { lines } => Some(lines),
+            GameMode::Marathon
+            | GameMode::Ultra { .. }
+            | GameMode::Dig { .. }
+            | GameMode::Training { .. } => None,
+        }
+    }
+
+    /// How long the game lasts, if it is timed.
+    pub const fn time_limit(self) -> Option<Duration> {
+        match self {
+            GameMode::Ultra { duration } => Some(duration),
+            GameMode::Marathon
+            | GameMode::Sprint { .. }
+            | GameMode::Dig { .. }
+            | GameMode::Training { .. } => None,
+        }
+    }
+
+    /// The garbage rows the board starts with.
+    pub const fn garbage_rows(self) -> u32 {
+        match self {
+            GameMode::Dig { rows, .. } => rows,
+            _ => 0,
+        }
+    }
+
+    /// Whether the game is practice, which is kept off the leaderboard.
+    pub const fn is_training(self) -> bool {
+        matches!(self, GameMode::Training { .. })
+    }
+
+    /// Whether the game is a race to a goal, ranked by time rather than
+    /// score.
+    pub const fn is_race(self) -> bool {
+        matches!(
+            self,
+            GameMode::Sprint { .. }
+                | GameMode::Dig {
+                    infinite: false,
+                    ..
+                }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{
+        End, Engine, Event, GARBAGE_COLOR, GRID_WIDTH, Held, Input, Rotation, Ruleset, Tetromino,
+        TetrominoKind,
+    };
+
+    use glam::IVec2;
+
+    fn engine(mode: GameMode) -> Engine {
+        Engine::new(
+            Ruleset {
+                mode,
+                ..Ruleset::default()
+            },
+            1,
+        )
+    }
+
+    /// Drops an upright I down the right wall.
+    fn drop_i(engine: &mut Engine) -> Vec<Event> {
+        engine.active = Tetromino::new(TetrominoKind::I);
+        engine.active.rotation = Rotation::East;
+        engine.active.position.x = GRID_WIDTH - 2;
+
+        engine.apply(Input::HardDrop)
+    }
+
+    #[test]
+    fn sprints_end_at_the_goal_with_splits() {
+        let mut engine = engine(GameMode::Sprint { lines: 10 });
+        for y in 0..12 {
+            for x in 0..GRID_WIDTH - 1 {
+                engine.grid.tiles.insert(IVec2::new(x, y), GARBAGE_COLOR);
+            }
+        }
+
+        engine.update(Duration::from_secs(5), Held::default());
+        drop_i(&mut engine);
+        drop_i(&mut engine);
+        assert!(!engine.is_game_over());
+
+        engine.update(Duration::from_secs(5), Held::default());
+        let events = drop_i(&mut engine);
+
+        let at = Duration::from_secs(10);
+        assert!(events.contains(&Event::Split(at)));
+        assert!(events.contains(&Event::GameOver(End::Completed)));
+        assert_eq!(engine.splits(), [at]);
+        assert_eq!(engine.lines(), 12);
+    }
+
+    #[test]
+    fn only_sprints_have_a_goal_and_only_ultras_a_limit() {
+        for mode in GameMode::ALL {
+            assert_eq!(
+                mode.goal_lines().is_some(),
+                matches!(mode, GameMode::Sprint { .. })
+            );
+            assert_eq!(
+                mode.time_limit().is_some(),
+                matches!(mode, GameMode::Ultra { .. })
+            );
+        }
+    }
+
+    #[test]
+    fn ultras_end_when_time_runs_out() {
+        let limit = Duration::from_secs(2);
+        let mut engine = engine(GameMode::Ultra { duration: limit });
+
+        engine.update(Duration::from_secs(1), Held::default());
+        assert!(!engine.is_game_over());
+
+        let events = engine.update(Duration::from_millis(1500), Held::default());
+        assert!(events.contains(&Event::GameOver(End::TimeUp)));
+        assert_eq!(engine.end(), Some(End::TimeUp));
+        // the clock stops at the limit rather than the frame that passed it
+        assert_eq!(engine.time(), limit);
+
+        assert!(
+            engine
+                .update(Duration::from_secs(1), Held::default())
+                .is_empty()
+        );
+        assert_eq!(engine.time(), limit);
+    }
+
+    /// Fills the hole in each garbage row, then drops an O on top to lock
+    /// them all in one clear.
+    fn clear_garbage(engine: &mut Engine) -> Vec<Event> {
+        for y in 0..engine.garbage as i32 {
+            for x in 0..GRID_WIDTH {
+                engine
+                    .grid
+                    .tiles
+                    .entry(IVec2::new(x, y))
+                    .or_insert(GARBAGE_COLOR);
+            }
+        }
+
+        engine.active = Tetromino::new(TetrominoKind::O);
+        engine.active.position = IVec2::new(0, 8);
+
+        engine.apply(Input::HardDrop)
+    }
+
+    /// The number of tiles in each of the bottom `rows` rows.
+    fn row_counts(engine: &Engine, rows: i32) -> Vec<usize> {
+        (0..rows)
+            .map(|y| engine.grid.tiles.keys().filter(|pos| pos.y == y).count())
+            .collect()
+    }
+
+    #[test]
+    fn digs_start_with_a_hole_in_each_garbage_row() {
+        let engine = engine(GameMode::Dig {
+            rows: 3,
+            infinite: false,
+        });
+
+        assert_eq!(engine.garbage, 3);
+        assert_eq!(engine.grid.tiles.len(), 3 * (GRID_WIDTH as usize - 1));
+        assert_eq!(row_counts(&engine, 3), [GRID_WIDTH as usize - 1; 3]);
+    }
+
+    #[test]
+    fn digs_complete_once_the_garbage_is_gone() {
+        let mut engine = engine(GameMode::Dig {
+            rows: 3,
+            infinite: false,
+        });
+
+        let events = clear_garbage(&mut engine);
+        assert!(events.contains(&Event::GameOver(End::Completed)));
+        assert_eq!(engine.garbage, 0);
+    }
+
+    #[test]
+    fn infinite_digs_push_up_new_garbage() {
+        let mut engine = engine(GameMode::Dig {
+            rows: 3,
+            infinite: true,
+        });
+
+        clear_garbage(&mut engine);
+        assert!(!engine.is_game_over());
+        assert_eq!(engine.garbage, 3);
+        assert_eq!(row_counts(&engine, 3), [GRID_WIDTH as usize - 1; 3]);
+        // the O sits on the new garbage
+        assert_eq!(row_counts(&engine, 5)[3..], [2, 2]);
+    }
+}
@@ -0,0 +1,423 @@
+use catppuccin::ColorName;
+use glam::{IVec2, ivec2};
+use rand::distr::StandardUniform;
+use rand::prelude::*;
+
+use super::grid::{GRID_HEIGHT, GRID_WIDTH};
+
+pub const TETROMINOS: [TetrominoKind; 7] = [
+    TetrominoKind::I,
+    TetrominoKind::O,
+    TetrominoKind::T,
+    TetrominoKind::S,
+    TetrominoKind::Z,
+    TetrominoKind::J,
+    TetrominoKind::L,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rotation {
+    North,
+    South,
+    West,
+    East,
+}
+
+#[rustfmt::skip]
+#[allow(clippy::type_complexity)]
+pub const TETROMINO_SHAPES: [(TetrominoKind, [(Rotation, [IVec2; 4]); 4]); 7] = [
+    (
+        TetrominoKind::I,
+        [
+            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(2, 0)]),
+            (Rotation::East,  [ivec2(1, 1), ivec2(1, 0), ivec2(1, -1), ivec2(1, -2)]),
+            (Rotation::South, [ivec2(-1, -1), ivec2(0, -1), ivec2(1, -1), ivec2(2, -1)]),
+            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(0, -2)]),
+        ],
+    ),
+    (
+        TetrominoKind::O,
+        [
+            (Rotation::North, [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::East,  [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::South, [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::West,  [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
+        ],
+    ),
+    (
+        TetrominoKind::T,
+        [
+            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(0, 1)]),
+            (Rotation::East,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(1, 0)]),
+            (Rotation::South, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(0, -1)]),
+            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(-1, 0)]),
+        ],
+    ),
+    (
+        TetrominoKind::S,
+        [
+            (Rotation::North, [ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1), ivec2(0, -1)]),
+            (Rotation::East,  [ivec2(0, 1), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
+            (Rotation::South, [ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1), ivec2(0, -1)]),
+            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
+        ],
+    ),
+    (
+        TetrominoKind::Z,
+        [
+            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::East,  [ivec2(1, 1), ivec2(1, 0), ivec2(0, 0), ivec2(0, -1)]),
+            (Rotation::South, [ivec2(-1, 0), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::West,  [ivec2(1, 1), ivec2(1, 0), ivec2(0, 0), ivec2(0, -1)]),
+        ],
+    ),
+    (
+        TetrominoKind::J,
+        [
+            (Rotation::North, [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(-1, -1)]),
+            (Rotation::East,  [ivec2(-1, 1), ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0)]),
+            (Rotation::South, [ivec2(1, 1), ivec2(0, 1), ivec2(0, 0), ivec2(0, -1)]),
+            (Rotation::West,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
+        ],
+    ),
+    (
+        TetrominoKind::L,
+        [
+            (Rotation::North, [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
+            (Rotation::East,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1)]),
+            (Rotation::South, [ivec2(-1, 1), ivec2(0, 1), ivec2(0, 0), ivec2(0, -1)]),
+            (Rotation::West,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(1, 1)]),
+        ],
+    ),
+];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tetromino {
+    pub position: IVec2,
+    pub kind: TetrominoKind,
+    pub rotation: Rotation,
+}
+
+impl Tetromino {
+    pub fn new(kind: TetrominoKind) -> Self {
+        Tetromino {
+            position: IVec2::new(GRID_WIDTH / 2, GRID_HEIGHT),
+            kind,
+            rotation: Rotation::North,
+        }
+    }
+
+    pub const fn color(&self) -> ColorName {
+        self.kind.color()
+    }
+
+    pub fn move_left(&mut self) {
+        self.position.x -= 1;
+    }
+
+    pub fn move_right(&mut self) {
+        self.position.x += 1;
+    }
+
+    pub fn move_up(&mut self) {
+        self.position.y += 1;
+    }
+
+    pub fn move_down(&mut self) {
+        self.position.y -= 1;
+    }
+
+    pub fn rotate_left(&mut self) {
+        self.rotation = match self.rotation {
+            Rotation::North => Rotation::West,
+            Rotation::West => Rotation::South,
+            Rotation::South => Rotation::East,
+            Rotation::East => Rotation::North,
+        }
+    }
+
+    pub fn rotate_right(&mut self) {
+        self.rotation = match self.rotation {
+            Rotation::North => Rotation::East,
+            Rotation::East => Rotation::South,
+            Rotation::South => Rotation::West,
+            Rotation::West => Rotation::North,
+        }
+    }
+
+    pub fn rotate_180(&mut self) {
+        self.rotation = match self.rotation {
+            Rotation::North => Rotation::South,
+            Rotation::East => Rotation::West,
+            Rotation::South => Rotation::North,
+            Rotation::West => Rotation::East,
+        }
+    }
+
+    pub fn occupied_tiles(&self) -> [IVec2; 4] {
+        for (kind, rest) in TETROMINO_SHAPES {
+            if kind != self.kind {
+                continue;
+            }
+
+            for (rotation, tiles) in rest {
+                if rotation != self.rotation {
+                    continue;
+                }
+
+                return tiles.map(|position| position + self.position);
+            }
+        }
+
+        unreachable!()
+    }
+
+    pub fn is_in_ground(&self) -> bool {
+        self.occupied_tiles().iter().any(|&tile_pos| tile_pos.y < 0)
+    }
+
+    pub fn is_in_wall(&self) -> bool {
+        self.occupied_tiles()
+            .iter()
+            .any(|&tile_pos| tile_pos.x < 0 || tile_pos.x >= GRID_WIDTH)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TetrominoKind {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl Distribution<TetrominoKind> for StandardUniform {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TetrominoKind {
+        *TETROMINOS.choose(rng).unwrap()
+    }
+}
+
+impl TetrominoKind {
+    pub const fn color(&self) -> ColorName {
+        use ColorName::*;
+
+        match *self {
+            TetrominoKind::I => Teal,
+            TetrominoKind::O => Yellow,
+            TetrominoKind::T => Mauve,
+            TetrominoKind::S => Green,
+            TetrominoKind::Z => Red,
+            TetrominoKind::J => Peach,
+            TetrominoKind::L => Blue,
+        }
+    }
+}
+
+/// The Guideline has no kicks for 180 rotations, so they try the piece in
+/// place, then one row up, one column either side, and one row down.
+const HALF_TURN_KICKS: [IVec2; 5] = [
+    IVec2::ZERO,
+    IVec2::new(0, 1),
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, -1),
+];
+
+const fn is_half_turn(from: Rotation, to: Rotation) -> bool {
+    matches!(
+        (from, to),
+        (Rotation::North, Rotation::South)
+            | (Rotation::South, Rotation::North)
+            | (Rotation::East, Rotation::West)
+            | (Rotation::West, Rotation::East)
+    )
+}
+
+pub const fn get_wall_kick_offsets(
+    kind: TetrominoKind,
+    from: Rotation,
+    to: Rotation,
+) -> [IVec2; 5] {
+    match kind {
+        TetrominoKind::O => [IVec2::ZERO; 5],
+        _ if is_half_turn(from, to) => HALF_TURN_KICKS,
+        TetrominoKind::I => match (from, to) {
+            (Rotation::North, Rotation::East) => [
+                IVec2::ZERO,
+                IVec2::new(-2, 0),
+                IVec2::new(1, 0),
+                IVec2::new(-2, -1),
+                IVec2::new(1, 2),
+            ],
+            (Rotation::East, Rotation::North) => [
+                IVec2::ZERO,
+                IVec2::new(2, 0),
+                IVec2::new(-1, 0),
+                IVec2::new(2, 1),
+                IVec2::new(-1, -2),
+            ],
+            (Rotation::East, Rotation::South) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(-1, 2),
+                IVec2::new(2, -1),
+            ],
+            (Rotation::South, Rotation::East) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(-2, 0),
+                IVec2::new(1, -2),
+                IVec2::new(-2, 1),
+            ],
+            (Rotation::South, Rotation::West) => [
+                IVec2::ZERO,
+                IVec2::new(2, 0),
+                IVec2::new(-1, 0),
+                IVec2::new(2, 1),
+                IVec2::new(-1, -2),
+            ],
+            (Rotation::West, Rotation::South) => [
+                IVec2::ZERO,
+                IVec2::new(-2, 0),
+                IVec2::new(1, 0),
+                IVec2::new(-2, -1),
+                IVec2::new(1, 2),
+            ],
+            (Rotation::West, Rotation::North) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(-2, 0),
+                IVec2::new(1, -2),
+                IVec2::new(-2, 1),
+            ],
+            (Rotation::North, Rotation::West) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(2, 0),
+                IVec2::new(-1, 2),
+                IVec2::new(2, -1),
+            ],
+            _ => unreachable!(),
+        },
+        _ => match (from, to) {
+            (Rotation::North, Rotation::East) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(-1, 1),
+                IVec2::new(0, -2),
+                IVec2::new(-1, -2),
+            ],
+            (Rotation::East, Rotation::North) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(1, -1),
+                IVec2::new(0, 2),
+                IVec2::new(1, 2),
+            ],
+            (Rotation::East, Rotation::South) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(1, -1),
+                IVec2::new(0, 2),
+                IVec2::new(1, 2),
+            ],
+            (Rotation::South, Rotation::East) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(-1, 1),
+                IVec2::new(0, -2),
+                IVec2::new(-1, -2),
+            ],
+            (Rotation::South, Rotation::West) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(1, 1),
+                IVec2::new(0, -2),
+                IVec2::new(1, -2),
+            ],
+            (Rotation::West, Rotation::South) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(-1, -1),
+                IVec2::new(0, 2),
+                IVec2::new(-1, 2),
+            ],
+            (Rotation::West, Rotation::North) => [
+                IVec2::ZERO,
+                IVec2::new(1, 0),
+                IVec2::new(1, -1),
+                IVec2::new(0, 2),
+                IVec2::new(1, 2),
+            ],
+            (Rotation::North, Rotation::West) => [
+                IVec2::ZERO,
+                IVec2::new(-1, 0),
+                IVec2::new(-1, 1),
+                IVec2::new(0, -2),
+                IVec2::new(-1, -2),
+            ],
+            _ => unreachable!(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROTATIONS: [Rotation; 4] = [
+        Rotation::North,
+        Rotation::East,
+        Rotation::South,
+        Rotation::West,
+    ];
+
+    #[test]
+    fn kicks_try_the_piece_in_place_first() {
+        for kind in TETROMINOS {
+            for from in ROTATIONS {
+                for to in ROTATIONS.into_iter().filter(|&to| to != from) {
+                    assert_eq!(get_wall_kick_offsets(kind, from, to)[0], IVec2::ZERO);
+                }
+            }
+        }
+
+        assert_eq!(
+            get_wall_kick_offsets(TetrominoKind::O, Rotation::North, Rotation::East),
+            [IVec2::ZERO; 5]
+        );
+        assert_eq!(
+            get_wall_kick_offsets(TetrominoKind::T, Rotation::East, Rotation::West),
+            HALF_TURN_KICKS
+        );
+    }
+
+    #[test]
+    fn turning_back_undoes_each_kick() {
+        for kind in [TetrominoKind::I, TetrominoKind::T] {
+            for (index, &from) in ROTATIONS.iter().enumerate() {
+                let to = ROTATIONS[(index + 1) % 4];
+                let there = get_wall_kick_offsets(kind, from, to);
+                let back = get_wall_kick_offsets(kind, to, from);
+
+                assert_eq!(there.map(|offset| -offset), back, "{kind:?} {from:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn i_kicks_differ_from_the_rest() {
+        assert_eq!(
+            get_wall_kick_offsets(TetrominoKind::I, Rotation::North, Rotation::East)[1],
+            IVec2::new(-2, 0)
+        );
+        assert_eq!(
+            get_wall_kick_offsets(TetrominoKind::J, Rotation::North, Rotation::East)[1],
+            IVec2::new(-1, 0)
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use rand::prelude::*;
+
+use std::collections::VecDeque;
+
+use super::piece::{TETROMINOS, TetrominoKind};
+use super::randomizer::{Randomizer, RandomizerKind};
+
+/// The upcoming pieces, always kept `TETROMINOS.len()` long so a preview has
+/// something to show.
+pub struct PieceQueue {
+    pub upcoming: VecDeque<TetrominoKind>,
+    randomizer: Box<dyn Randomizer>,
+}
+
+impl PieceQueue {
+    pub fn new<R>(kind: RandomizerKind, rng: &mut R) -> Self
+    where
+        R: Rng,
+    {
+        let mut randomizer = kind.build();
+        let upcoming = (0..TETROMINOS.len())
+            .map(|_| randomizer.next(rng))
+            .collect();
+
+        PieceQueue {
+            upcoming,
+            randomizer,
+        }
+    }
+
+    pub fn next<R>(&mut self, rng: &mut R) -> TetrominoKind
+    where
+        R: Rng,
+    {
+        self.upcoming.push_back(self.randomizer.next(rng));
+        self.upcoming.pop_front().unwrap()
+    }
+}
@@ -0,0 +1,280 @@
+use rand::prelude::*;
+
+use std::collections::VecDeque;
+
+use super::piece::{TETROMINOS, TetrominoKind};
+
+/// Decides which piece comes next. Every implementation draws its randomness
+/// from the generator it is handed, so a seeded generator gives a
+/// reproducible sequence.
+pub trait Randomizer: Send + Sync {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetrominoKind;
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum RandomizerKind {
+    /// One of each piece per bag of seven.
+    #[default]
+    SevenBag,
+    /// Two of each piece per bag of fourteen.
+    FourteenBag,
+    /// TGM-style: reroll pieces that appear in the recent history.
+    History,
+    /// NES-style: reroll once on an immediate repeat.
+    Nes,
+    /// Every piece is drawn independently.
+    Random,
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 5] = [
+        RandomizerKind::SevenBag,
+        RandomizerKind::FourteenBag,
+        RandomizerKind::History,
+        RandomizerKind::Nes,
+        RandomizerKind::Random,
+    ];
+
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::History => Box::new(History::default()),
+            RandomizerKind::Nes => Box::new(Nes::default()),
+            RandomizerKind::Random => Box::new(Uniform),
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7-bag",
+            RandomizerKind::FourteenBag => "14-bag",
+            RandomizerKind::History => "TGM history",
+            RandomizerKind::Nes => "NES",
+            RandomizerKind::Random => "Random",
+        }
+    }
+}
+
+/// Deals `copies` of every piece in a shuffled bag and refills it once empty.
+pub struct Bag {
+    copies: usize,
+    bag: Vec<TetrominoKind>,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Bag {
+            copies,
+            bag: Vec::with_capacity(TETROMINOS.len() * copies),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetrominoKind {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(TETROMINOS);
+            }
+            self.bag.shuffle(rng);
+        }
+
+        self.bag.pop().unwrap()
+    }
+}
+
+/// The TGM randomizer: keeps the last four pieces and rolls up to
+/// `HISTORY_ROLLS` times in all while the roll is one of them. The first
+/// piece is never an S, Z or O.
+pub struct History {
+    history: VecDeque<TetrominoKind>,
+    first: bool,
+}
+
+const HISTORY_ROLLS: usize = 4;
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            history: VecDeque::from([
+                TetrominoKind::Z,
+                TetrominoKind::S,
+                TetrominoKind::S,
+                TetrominoKind::Z,
+            ]),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetrominoKind {
+        let piece = if self.first {
+            self.first = false;
+            *[
+                TetrominoKind::I,
+                TetrominoKind::T,
+                TetrominoKind::J,
+                TetrominoKind::L,
+            ]
+            .choose(rng)
+            .unwrap()
+        } else {
+            let mut piece = rng.random::<TetrominoKind>();
+            for _ in 1..HISTORY_ROLLS {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = rng.random::<TetrominoKind>();
+            }
+            piece
+        };
+
+        self.history.pop_front();
+        self.history.push_back(piece);
+
+        piece
+    }
+}
+
+/// The NES randomizer: rolls an eight-sided die where the eighth side, or a
+/// repeat of the previous piece, triggers a single seven-sided reroll.
+#[derive(Default)]
+pub struct Nes {
+    previous: Option<TetrominoKind>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetrominoKind {
+        let roll = rng.random_range(0..=TETROMINOS.len());
+
+        let piece = match TETROMINOS.get(roll) {
+            Some(&piece) if Some(piece) != self.previous => piece,
+            _ => rng.random::<TetrominoKind>(),
+        };
+
+        self.previous = Some(piece);
+
+        piece
+    }
+}
+
+/// Draws every piece independently and uniformly.
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+    fn next(&mut self, rng: &mut dyn RngCore) -> TetrominoKind {
+        rng.random::<TetrominoKind>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+
+    const SAMPLES: usize = 70_000;
+
+    fn sample(kind: RandomizerKind, seed: u64) -> Vec<TetrominoKind> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut randomizer = kind.build();
+        (0..SAMPLES).map(|_| randomizer.next(&mut rng)).collect()
+    }
+
+    fn counts(pieces: &[TetrominoKind]) -> [usize; 7] {
+        let mut counts = [0; 7];
+        for piece in pieces {
+            counts[TETROMINOS.iter().position(|p| p == piece).unwrap()] += 1;
+        }
+        counts
+    }
+
+    /// Pearson's chi-squared statistic against a uniform distribution.
+    fn chi_squared(pieces: &[TetrominoKind]) -> f64 {
+        let expected = pieces.len() as f64 / 7.0;
+        counts(pieces)
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    fn repeat_rate(pieces: &[TetrominoKind]) -> f64 {
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        repeats as f64 / (pieces.len() - 1) as f64
+    }
+
+    fn longest_drought(pieces: &[TetrominoKind]) -> usize {
+        TETROMINOS
+            .iter()
+            .map(|kind| {
+                pieces
+                    .split(|piece| piece == kind)
+                    .map(<[_]>::len)
+                    .max()
+                    .unwrap()
+            })
+            .max()
+            .unwrap()
+    }
+
+    // 6 degrees of freedom, p = 0.001
+    const CHI_SQUARED_CRITICAL: f64 = 22.46;
+
+    #[test]
+    fn seven_bag_deals_every_piece_once_per_bag() {
+        let pieces = sample(RandomizerKind::SevenBag, 1);
+
+        for bag in pieces.chunks(7) {
+            assert_eq!(counts(bag), [1; 7]);
+        }
+        assert!(longest_drought(&pieces) <= 12);
+    }
+
+    #[test]
+    fn fourteen_bag_deals_every_piece_twice_per_bag() {
+        let pieces = sample(RandomizerKind::FourteenBag, 2);
+
+        for bag in pieces.chunks(14) {
+            assert_eq!(counts(bag), [2; 7]);
+        }
+        assert!(longest_drought(&pieces) <= 24);
+    }
+
+    #[test]
+    fn history_avoids_recent_pieces() {
+        let pieces = sample(RandomizerKind::History, 3);
+
+        assert!(chi_squared(&pieces) < CHI_SQUARED_CRITICAL);
+        // a pure random sequence repeats 1/7 of the time
+        assert!(repeat_rate(&pieces) < 0.04);
+    }
+
+    #[test]
+    fn history_never_starts_with_s_z_or_o() {
+        for seed in 0..1000 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let first = RandomizerKind::History.build().next(&mut rng);
+            assert!(![TetrominoKind::S, TetrominoKind::Z, TetrominoKind::O].contains(&first));
+        }
+    }
+
+    #[test]
+    fn nes_rerolls_repeats_once() {
+        let pieces = sample(RandomizerKind::Nes, 4);
+
+        // (2/8) * (1/7) = 1/28
+        let rate = repeat_rate(&pieces);
+        assert!((0.025..0.047).contains(&rate), "repeat rate {rate}");
+    }
+
+    #[test]
+    fn random_is_uniform() {
+        let pieces = sample(RandomizerKind::Random, 5);
+
+        assert!(chi_squared(&pieces) < CHI_SQUARED_CRITICAL);
+        let rate = repeat_rate(&pieces);
+        assert!((0.13..0.155).contains(&rate), "repeat rate {rate}");
+    }
+}
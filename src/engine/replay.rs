@@ -0,0 +1,564 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::{
+    Delays, Engine, Event, GameMode, Handling, Held, Input, Leveling, LockDelay, LockReset,
+    RandomizerKind, Ruleset,
+};
+
+/// Marks the start of every replay file.
+const MAGIC: &[u8; 4] = b"TBRR";
+
+/// The version of the replay format that [`Replay::to_bytes`] writes. Bump
+/// it whenever the layout changes.
+pub const REPLAY_VERSION: u16 = 2;
+
+/// The oldest version of the replay format that can still be read. Version
+/// 1 came before [`Ruleset::delays`], so its games had none.
+const OLDEST_REPLAY_VERSION: u16 = 1;
+
+/// A recorded game: its seed and rules, and the inputs of every fixed tick.
+/// Playing the inputs back through an [`Engine`] built from the same seed
+/// and rules ends in the same board and score.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    pub handling: Handling,
+    /// The fixed timestep the game was advanced by.
+    pub tick: Duration,
+    /// How many ticks the game lasted.
+    pub ticks: u32,
+    /// What happened at each tick. Held inputs are only recorded when they
+    /// change.
+    pub records: Vec<(u32, Record)>,
+    /// The held inputs of the last recorded tick.
+    held: Held,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Record {
+    /// Applied at the start of the tick.
+    Input(Input),
+    /// Held from this tick on.
+    Held(Held),
+}
+
+impl Replay {
+    pub fn new(seed: u64, ruleset: Ruleset, handling: Handling, tick: Duration) -> Self {
+        Replay {
+            seed,
+            ruleset,
+            handling,
+            tick,
+            ticks: 0,
+            records: Vec::new(),
+            held: Held::default(),
+        }
+    }
+
+    /// A fresh engine to play the replay back on.
+    pub fn engine(&self) -> Engine {
+        let mut engine = Engine::new(self.ruleset, self.seed);
+        engine.set_handling(self.handling);
+        engine
+    }
+
+    /// Records a tick that applied `inputs` and then advanced with `held`.
+    pub fn record(&mut self, inputs: &[Input], held: Held) {
+        let tick = self.ticks;
+
+        self.records
+            .extend(inputs.iter().map(|&input| (tick, Record::Input(input))));
+
+        if held != self.held {
+            self.held = held;
+            self.records.push((tick, Record::Held(held)));
+        }
+
+        self.ticks += 1;
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer(Vec::new());
+
+        writer.0.extend_from_slice(MAGIC);
+        writer.u16(REPLAY_VERSION);
+        writer.u64(self.seed);
+        writer.ruleset(&self.ruleset);
+        writer.handling(&self.handling);
+        writer.duration(self.tick);
+        writer.u32(self.ticks);
+
+        writer.u32(self.records.len() as u32);
+        for &(tick, record) in &self.records {
+            writer.u32(tick);
+            writer.u8(record_code(record));
+        }
+
+        writer.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = Reader(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+
+        let version = reader.u16()?;
+        if !(OLDEST_REPLAY_VERSION..=REPLAY_VERSION).contains(&version) {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        let seed = reader.u64()?;
+        let ruleset = reader.ruleset(version)?;
+        let handling = reader.handling()?;
+        let tick = reader.duration()?;
+        let ticks = reader.u32()?;
+
+        let count = reader.u32()?;
+        let mut records = Vec::new();
+        let mut held = Held::default();
+        for _ in 0..count {
+            let at = reader.u32()?;
+            let record = decode_record(reader.u8()?)?;
+            if let Record::Held(now_held) = record {
+                held = now_held;
+            }
+            records.push((at, record));
+        }
+
+        if !reader.0.is_empty() {
+            return Err(ReplayError::Invalid("trailing bytes"));
+        }
+
+        Ok(Replay {
+            seed,
+            ruleset,
+            handling,
+            tick,
+            ticks,
+            records,
+            held,
+        })
+    }
+}
+
+/// Steps an [`Engine`] through a [`Replay`] one tick at a time.
+pub struct Playback {
+    replay: Replay,
+    tick: u32,
+    next_record: usize,
+    held: Held,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay,
+            tick: 0,
+            next_record: 0,
+            held: Held::default(),
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// The number of ticks played back so far.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.ticks
+    }
+
+    /// Plays back the next tick on `engine` the same way it was recorded.
+    pub fn step(&mut self, engine: &mut Engine) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.is_finished() {
+            return events;
+        }
+
+        while let Some(&(at, record)) = self.replay.records.get(self.next_record)
+            && at == self.tick
+        {
+            match record {
+                Record::Input(input) => events.extend(engine.apply(input)),
+                Record::Held(held) => self.held = held,
+            }
+            self.next_record += 1;
+        }
+
+        events.extend(engine.update(self.replay.tick, self.held));
+        self.tick += 1;
+
+        events
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayError {
+    NotAReplay,
+    /// Written by a different version of the game.
+    UnsupportedVersion(u16),
+    /// The file ends part way through.
+    Truncated,
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay format version {version} is not supported, expected {REPLAY_VERSION}"
+            ),
+            ReplayError::Truncated => write!(f, "replay file is truncated"),
+            ReplayError::Invalid(what) => write!(f, "invalid replay: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// The code of each input is its index, so new inputs go at the end.
+const INPUTS: [Input; 8] = [
+    Input::MoveLeft,
+    Input::MoveRight,
+    Input::SoftDrop,
+    Input::HardDrop,
+    Input::RotateLeft,
+    Input::RotateRight,
+    Input::Hold,
+    Input::Rotate180,
+];
+
+/// Set on the code of a [`Record::Held`], whose low bits are the held
+/// inputs.
+const HELD_FLAG: u8 = 0x80;
+
+fn record_code(record: Record) -> u8 {
+    match record {
+        Record::Input(input) => INPUTS.iter().position(|&i| i == input).unwrap() as u8,
+        Record::Held(held) => {
+            HELD_FLAG | held.left as u8 | (held.right as u8) << 1 | (held.soft_drop as u8) << 2
+        }
+    }
+}
+
+fn decode_record(code: u8) -> Result<Record, ReplayError> {
+    if code & HELD_FLAG != 0 {
+        return Ok(Record::Held(Held {
+            left: code & 1 != 0,
+            right: code & 2 != 0,
+            soft_drop: code & 4 != 0,
+        }));
+    }
+
+    INPUTS
+        .get(code as usize)
+        .map(|&input| Record::Input(input))
+        .ok_or(ReplayError::Invalid("unknown input"))
+}
+
+const LOCK_RESETS: [LockReset; 3] = [LockReset::Move, LockReset::Step, LockReset::Entry];
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn duration(&mut self, value: Duration) {
+        self.u64(value.as_nanos() as u64);
+    }
+
+    fn ruleset(&mut self, ruleset: &Ruleset) {
+        let randomizer = RandomizerKind::ALL
+            .iter()
+            .position(|&k| k == ruleset.randomizer);
+        self.u8(randomizer.unwrap() as u8);
+
+        self.duration(ruleset.lock_delay.duration);
+        self.u32(ruleset.lock_delay.max_resets);
+        let reset = LOCK_RESETS
+            .iter()
+            .position(|&r| r == ruleset.lock_delay.reset);
+        self.u8(reset.unwrap() as u8);
+
+        self.duration(ruleset.delays.line_clear);
+        self.duration(ruleset.delays.entry);
+
+        self.u32(ruleset.leveling.start_level);
+        self.u32(ruleset.leveling.lines_per_level);
+        self.u8(ruleset.partial_lock_out as u8);
+
+        match ruleset.mode {
+            GameMode::Marathon => self.u8(0),
+            GameMode::Sprint { lines } => {
+                self.u8(1);
+                self.u32(lines);
+            }
+            GameMode::Ultra { duration } => {
+                self.u8(2);
+                self.duration(duration);
+            }
+            GameMode::Dig { rows, infinite } => {
+                self.u8(3);
+                self.u32(rows);
+                self.u8(infinite as u8);
+            }
+            GameMode::Training { restart } => {
+                self.u8(4);
+                self.u8(restart as u8);
+            }
+        }
+    }
+
+    fn handling(&mut self, handling: &Handling) {
+        self.duration(handling.das);
+        self.duration(handling.arr);
+        self.u32(handling.soft_drop_factor);
+        self.duration(handling.das_cut);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        if self.0.len() < len {
+            return Err(ReplayError::Truncated);
+        }
+
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ReplayError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ReplayError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn bool(&mut self) -> Result<bool, ReplayError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ReplayError::Invalid("expected a boolean")),
+        }
+    }
+
+    fn duration(&mut self) -> Result<Duration, ReplayError> {
+        Ok(Duration::from_nanos(self.u64()?))
+    }
+
+    fn ruleset(&mut self, version: u16) -> Result<Ruleset, ReplayError> {
+        let randomizer = *RandomizerKind::ALL
+            .get(self.u8()? as usize)
+            .ok_or(ReplayError::Invalid("unknown randomizer"))?;
+
+        let lock_delay = LockDelay {
+            duration: self.duration()?,
+            max_resets: self.u32()?,
+            reset: *LOCK_RESETS
+                .get(self.u8()? as usize)
+                .ok_or(ReplayError::Invalid("unknown lock reset"))?,
+        };
+
+        let delays = if version >= 2 {
+            Delays {
+                line_clear: self.duration()?,
+                entry: self.duration()?,
+            }
+        } else {
+            Delays::NONE
+        };
+
+        let leveling = Leveling {
+            start_level: self.u32()?,
+            lines_per_level: self.u32()?,
+        };
+
+        let partial_lock_out = self.bool()?;
+
+        let mode = match self.u8()? {
+            0 => GameMode::Marathon,
+            1 => GameMode::Sprint { lines: self.u32()? },
+            2 => GameMode::Ultra {
+                duration: self.duration()?,
+            },
+            3 => GameMode::Dig {
+                rows: self.u32()?,
+                infinite: self.bool()?,
+            },
+            4 => GameMode::Training {
+                restart: self.bool()?,
+            },
+            _ => return Err(ReplayError::Invalid("unknown game mode")),
+        };
+
+        Ok(Ruleset {
+            mode,
+            randomizer,
+            lock_delay,
+            delays,
+            leveling,
+            partial_lock_out,
+        })
+    }
+
+    fn handling(&mut self) -> Result<Handling, ReplayError> {
+        Ok(Handling {
+            das: self.duration()?,
+            arr: self.duration()?,
+            soft_drop_factor: self.u32()?,
+            das_cut: self.duration()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    const TICK: Duration = Duration::from_nanos(15_625_000);
+
+    /// Plays a game of random inputs, recording it as it goes.
+    fn play(ruleset: Ruleset, seed: u64, ticks: u32) -> (Engine, Replay) {
+        let mut engine = Engine::new(ruleset, seed);
+        let mut replay = Replay::new(seed, ruleset, *engine.handling(), TICK);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for _ in 0..ticks {
+            let inputs: Vec<Input> = (0..rng.random_range(0..3))
+                .map(|_| *INPUTS.choose(&mut rng).unwrap())
+                .collect();
+            let held = Held {
+                left: rng.random_bool(0.2),
+                right: rng.random_bool(0.2),
+                soft_drop: rng.random_bool(0.3),
+            };
+
+            for &input in &inputs {
+                engine.apply(input);
+            }
+            engine.update(TICK, held);
+            replay.record(&inputs, held);
+        }
+
+        (engine, replay)
+    }
+
+    fn play_back(replay: Replay) -> Engine {
+        let mut engine = replay.engine();
+        let mut playback = Playback::new(replay);
+        while !playback.is_finished() {
+            playback.step(&mut engine);
+        }
+        engine
+    }
+
+    #[test]
+    fn playback_ends_in_the_same_game() {
+        for mode in GameMode::ALL {
+            let ruleset = Ruleset {
+                mode,
+                ..Ruleset::default()
+            };
+            let (played, replay) = play(ruleset, 7, 4000);
+            let replayed = play_back(replay);
+
+            assert!(played.pieces() > 0);
+            assert!(played.grid() == replayed.grid(), "{mode:?}");
+            assert_eq!(played.score(), replayed.score(), "{mode:?}");
+            assert_eq!(played.lines(), replayed.lines(), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn playback_waits_out_the_same_delays() {
+        for delays in Delays::PRESETS {
+            let ruleset = Ruleset {
+                delays,
+                ..Ruleset::default()
+            };
+            let (played, replay) = play(ruleset, 11, 4000);
+            let replayed = play_back(replay);
+
+            assert!(played.grid() == replayed.grid(), "{delays:?}");
+            assert_eq!(played.score(), replayed.score(), "{delays:?}");
+        }
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let ruleset = Ruleset {
+            mode: GameMode::Dig {
+                rows: 8,
+                infinite: true,
+            },
+            randomizer: RandomizerKind::History,
+            delays: Delays::TGM,
+            partial_lock_out: true,
+            ..Ruleset::default()
+        };
+        let (_, replay) = play(ruleset, 42, 500);
+
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()), Ok(replay));
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        let (_, replay) = play(Ruleset::default(), 1, 10);
+        let mut bytes = replay.to_bytes();
+
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ReplayError::Truncated)
+        );
+
+        bytes[4] = 99;
+        assert_eq!(
+            Replay::from_bytes(&bytes),
+            Err(ReplayError::UnsupportedVersion(99))
+        );
+
+        assert_eq!(
+            Replay::from_bytes(b"not a replay"),
+            Err(ReplayError::NotAReplay)
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use glam::{IVec2, ivec2};
+
+use super::grid::Grid;
+use super::piece::{Rotation, Tetromino, TetrominoKind};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Spin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// What a single lock achieved.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Clear {
+    pub lines: u32,
+    pub spin: Spin,
+}
+
+impl Clear {
+    pub const fn new(lines: u32, spin: Spin) -> Self {
+        Clear { lines, spin }
+    }
+
+    /// Guideline points before the level multiplier.
+    pub const fn points(&self) -> u32 {
+        match (self.spin, self.lines) {
+            (Spin::None, 0) => 0,
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, _) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, _) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, _) => 1600,
+        }
+    }
+
+    /// Tetrises and T-spins, which keep a back-to-back chain going.
+    pub const fn is_difficult(&self) -> bool {
+        self.lines >= 4 || !matches!(self.spin, Spin::None)
+    }
+}
+
+/// Guideline points for the `combo`th line-clearing lock in a row, counting
+/// from zero, before the level multiplier.
+pub const fn combo_points(combo: u32) -> u32 {
+    50 * combo
+}
+
+/// Guideline points for clearing `lines` that leave the board empty, before
+/// the level multiplier. These are added on top of the clear's own points.
+pub const fn perfect_clear_points(lines: u32, back_to_back: bool) -> u32 {
+    match lines {
+        0 => 0,
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        _ if back_to_back => 3200,
+        _ => 2000,
+    }
+}
+
+/// The index of the last SRS kick test, which always turns a mini into a
+/// full T-spin.
+const LAST_KICK: usize = 4;
+
+/// Classifies a T piece about to lock with the 3-corner rule. `last_kick` is
+/// the kick test index used if the last successful action was a rotation.
+pub fn detect_spin(grid: &Grid, tetromino: &Tetromino, last_kick: Option<usize>) -> Spin {
+    let Some(kick) = last_kick else {
+        return Spin::None;
+    };
+
+    if tetromino.kind != TetrominoKind::T {
+        return Spin::None;
+    }
+
+    let is_blocked = |offset: IVec2| grid.is_blocked(tetromino.position + offset);
+
+    let corners = [ivec2(-1, 1), ivec2(1, 1), ivec2(1, -1), ivec2(-1, -1)];
+    if corners
+        .into_iter()
+        .filter(|&corner| is_blocked(corner))
+        .count()
+        < 3
+    {
+        return Spin::None;
+    }
+
+    // the two corners either side of the T's pointing tile
+    let front = match tetromino.rotation {
+        Rotation::North => [ivec2(-1, 1), ivec2(1, 1)],
+        Rotation::East => [ivec2(1, 1), ivec2(1, -1)],
+        Rotation::South => [ivec2(1, -1), ivec2(-1, -1)],
+        Rotation::West => [ivec2(-1, -1), ivec2(-1, 1)],
+    };
+
+    if front.into_iter().all(is_blocked) || kick == LAST_KICK {
+        Spin::Full
+    } else {
+        Spin::Mini
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::{Engine, Event, GARBAGE_COLOR, Input, Ruleset};
+
+    /// A grid drawn with `#` for locked tiles, top row first and the bottom
+    /// row last.
+    fn board(rows: &[&str]) -> Grid {
+        let mut grid = Grid::default();
+
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                if tile == '#' {
+                    grid.tiles.insert(ivec2(x as i32, y as i32), GARBAGE_COLOR);
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn t_piece(rotation: Rotation, position: IVec2) -> Tetromino {
+        Tetromino {
+            position,
+            kind: TetrominoKind::T,
+            rotation,
+        }
+    }
+
+    /// Locks a T spun into `position` on `grid` by the kick test `kick`,
+    /// returning what it cleared.
+    fn spin_in(grid: Grid, rotation: Rotation, position: IVec2, kick: usize) -> Vec<Event> {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.grid = grid;
+        engine.active = t_piece(rotation, position);
+        engine.last_kick = Some(kick);
+
+        engine.apply(Input::HardDrop)
+    }
+
+    fn cleared(events: &[Event]) -> Option<Clear> {
+        events.iter().find_map(|event| match event {
+            Event::Cleared(clear) => Some(*clear),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn needs_three_corners() {
+        let grid = board(&["#.#.......", "...#######", "#.########"]);
+        let slot = ivec2(1, 1);
+
+        assert_eq!(
+            detect_spin(&grid, &t_piece(Rotation::South, slot), Some(0)),
+            Spin::Full
+        );
+        // a T that was moved or dropped in last didn't spin
+        assert_eq!(
+            detect_spin(&grid, &t_piece(Rotation::South, slot), None),
+            Spin::None
+        );
+
+        let open = board(&["#.........", "...#######", "..########"]);
+        assert_eq!(
+            detect_spin(&open, &t_piece(Rotation::South, slot), Some(0)),
+            Spin::None
+        );
+
+        let mut other = t_piece(Rotation::South, slot);
+        other.kind = TetrominoKind::J;
+        assert_eq!(detect_spin(&grid, &other, Some(0)), Spin::None);
+    }
+
+    #[test]
+    fn splits_mini_and_full_by_the_front_corners() {
+        // only one of the corners either side of the point is filled
+        let grid = board(&["#.#.......", "...#######", "#..#######"]);
+        let piece = t_piece(Rotation::South, ivec2(1, 1));
+
+        assert_eq!(detect_spin(&grid, &piece, Some(0)), Spin::Mini);
+        assert_eq!(detect_spin(&grid, &piece, Some(LAST_KICK)), Spin::Full);
+    }
+
+    #[test]
+    fn scores_t_spin_singles_and_doubles() {
+        let double = board(&["#.#.......", "...#######", "#.########"]);
+        let events = spin_in(double, Rotation::South, ivec2(1, 1), 0);
+        assert_eq!(cleared(&events), Some(Clear::new(2, Spin::Full)));
+
+        let single = board(&["#.#.......", "...######.", "#.########"]);
+        let events = spin_in(single, Rotation::South, ivec2(1, 1), 0);
+        assert_eq!(cleared(&events), Some(Clear::new(1, Spin::Full)));
+
+        let zero = board(&["#.#.......", "...######.", "#.#######."]);
+        let events = spin_in(zero, Rotation::South, ivec2(1, 1), 0);
+        assert_eq!(cleared(&events), Some(Clear::new(0, Spin::Full)));
+    }
+
+    #[test]
+    fn scores_a_mini() {
+        // a flat T tucked into the corner, its point under an open corner
+        let grid = board(&["#.........", "...#######"]);
+        let events = spin_in(grid, Rotation::North, ivec2(1, 0), 0);
+
+        assert_eq!(cleared(&events), Some(Clear::new(1, Spin::Mini)));
+    }
+
+    #[test]
+    fn the_last_kick_spins_a_triple() {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.grid = board(&[
+            ".#........",
+            "..........",
+            "#.########",
+            "#..#######",
+            "#.########",
+        ]);
+        engine.active = t_piece(Rotation::North, ivec2(2, 3));
+
+        engine.apply(Input::RotateRight);
+        assert_eq!(engine.last_kick, Some(LAST_KICK));
+        assert_eq!(engine.active().position, ivec2(1, 1));
+
+        let events = engine.apply(Input::HardDrop);
+        assert_eq!(cleared(&events), Some(Clear::new(3, Spin::Full)));
+        assert_eq!(engine.score().0, Clear::new(3, Spin::Full).points());
+    }
+
+    /// Drops a flat I across columns 4 to 7, or an upright one down the
+    /// right wall.
+    fn drop_i(engine: &mut Engine, upright: bool) -> Vec<Event> {
+        engine.active = Tetromino::new(TetrominoKind::I);
+        if upright {
+            engine.active.rotation = Rotation::East;
+            engine.active.position.x = 8;
+        }
+
+        engine.apply(Input::HardDrop)
+    }
+
+    #[test]
+    fn counts_combos_until_a_lock_clears_nothing() {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.grid = board(&["#.........", "####....##", "####....##", "####....##"]);
+
+        assert!(
+            !drop_i(&mut engine, false)
+                .iter()
+                .any(|e| matches!(e, Event::Combo(_)))
+        );
+        assert!(drop_i(&mut engine, false).contains(&Event::Combo(1)));
+        assert!(drop_i(&mut engine, false).contains(&Event::Combo(2)));
+        assert_eq!(
+            engine.score().0,
+            3 * 100 + combo_points(1) + combo_points(2)
+        );
+        assert_eq!(engine.stats().longest_combo, 2);
+
+        // an upright I that clears nothing breaks the combo
+        engine.grid = board(&["#.........", "####....##"]);
+        drop_i(&mut engine, true);
+        assert!(
+            !drop_i(&mut engine, false)
+                .iter()
+                .any(|e| matches!(e, Event::Combo(_)))
+        );
+    }
+
+    #[test]
+    fn chains_difficult_clears_back_to_back() {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.grid = board(&[
+            "#.........",
+            "#########.",
+            "#########.",
+            "#########.",
+            "#########.",
+            "####....##",
+            "#########.",
+            "#########.",
+            "#########.",
+            "#########.",
+        ]);
+        let tetris = Clear::new(4, Spin::None);
+
+        let events = drop_i(&mut engine, true);
+        assert!(events.contains(&Event::Cleared(tetris)));
+        assert!(!events.contains(&Event::BackToBack(tetris)));
+
+        // a single in between breaks the chain
+        drop_i(&mut engine, false);
+        let events = drop_i(&mut engine, true);
+        assert!(!events.contains(&Event::BackToBack(tetris)));
+
+        engine.grid = board(&[
+            "#.........",
+            "#########.",
+            "#########.",
+            "#########.",
+            "#########.",
+        ]);
+        let score = engine.score().0;
+        let events = drop_i(&mut engine, true);
+        assert!(events.contains(&Event::BackToBack(tetris)));
+        assert_eq!(
+            engine.score().0 - score,
+            tetris.points() * 3 / 2 + combo_points(3)
+        );
+    }
+
+    #[test]
+    fn rewards_emptying_the_board() {
+        let mut engine = Engine::new(Ruleset::default(), 1);
+        engine.grid = board(&["####....##"]);
+
+        let events = drop_i(&mut engine, false);
+
+        assert!(events.contains(&Event::PerfectClear));
+        assert!(engine.grid().tiles.is_empty());
+        assert_eq!(engine.score().0, 100 + perfect_clear_points(1, false));
+    }
+}
@@ -0,0 +1,36 @@
+use super::scoring::Clear;
+
+/// Counts kept over a game, beyond what the score needs.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Stats {
+    /// Every input applied, with a held direction counted once however far
+    /// it auto shifts.
+    pub inputs: u32,
+    /// How many times each kind of clear was made.
+    clears: Vec<(Clear, u32)>,
+    /// The most line-clearing locks in a row after the first.
+    pub longest_combo: u32,
+    /// Pieces dropped with more moves and rotations than they needed.
+    pub finesse_faults: u32,
+}
+
+impl Stats {
+    /// How many times `clear` was made.
+    pub fn clears(&self, clear: Clear) -> u32 {
+        self.clears
+            .iter()
+            .find(|(counted, _)| *counted == clear)
+            .map_or(0, |&(_, count)| count)
+    }
+
+    pub(super) fn count_clear(&mut self, clear: Clear) {
+        match self
+            .clears
+            .iter_mut()
+            .find(|(counted, _)| *counted == clear)
+        {
+            Some((_, count)) => *count += 1,
+            None => self.clears.push((clear, 1)),
+        }
+    }
+}
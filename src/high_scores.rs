@@ -0,0 +1,421 @@
+//! The best games of each mode, kept in a text file in the data directory
+//! so they outlast the session.
+
+use bevy::prelude::*;
+
+use tetris_rust::engine::GameMode;
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fmt, fs, io};
+
+use crate::data_dir;
+
+/// How many games are kept for each mode.
+pub const HIGH_SCORES_KEPT: usize = 10;
+
+/// The first line of the file, followed by the format version.
+const HEADER: &str = "tetris-rust high scores";
+
+/// The version of the file format, bumped whenever it changes.
+const HIGH_SCORES_VERSION: u32 = 1;
+
+/// A finished game worth remembering.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HighScore {
+    pub mode: GameMode,
+    pub score: u32,
+    pub lines: u32,
+    pub time: Duration,
+    /// When the game was played, in seconds since the Unix epoch.
+    pub date: u64,
+    pub seed: u64,
+    pub name: String,
+}
+
+impl HighScore {
+    /// Races rank by the fastest time, everything else by the highest
+    /// score, with the faster game first on a tie.
+    fn rank(&self, other: &HighScore) -> Ordering {
+        if self.mode.is_race() {
+            self.time.cmp(&other.time)
+        } else {
+            other
+                .score
+                .cmp(&self.score)
+                .then(self.time.cmp(&other.time))
+        }
+    }
+}
+
+/// The high score table of every mode.
+#[derive(Resource, Default, PartialEq, Debug)]
+pub struct HighScores(Vec<HighScore>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HighScoresError {
+    /// The file doesn't start with the high scores header.
+    NotHighScores,
+    /// The file was written by a newer version of the game.
+    UnsupportedVersion(u32),
+    /// The line with this number can't be read.
+    Invalid(usize),
+}
+
+impl fmt::Display for HighScoresError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HighScoresError::NotHighScores => write!(f, "not a high scores file"),
+            HighScoresError::UnsupportedVersion(version) => {
+                write!(f, "unsupported high scores version {version}")
+            }
+            HighScoresError::Invalid(line) => write!(f, "invalid high score on line {line}"),
+        }
+    }
+}
+
+impl std::error::Error for HighScoresError {}
+
+fn high_scores_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("high_scores.txt"))
+}
+
+impl HighScores {
+    /// Loads the saved high scores, starting afresh if there are none or
+    /// they can't be read.
+    pub fn load() -> Self {
+        let Some(path) = high_scores_path() else {
+            return HighScores::default();
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return HighScores::default();
+            }
+            Err(error) => {
+                warn!(
+                    "could not read high scores from {}: {error}",
+                    path.display()
+                );
+                return HighScores::default();
+            }
+        };
+
+        HighScores::from_text(&text).unwrap_or_else(|error| {
+            warn!("ignoring high scores in {}: {error}", path.display());
+            HighScores::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let Some(path) = high_scores_path() else {
+            warn!("no data directory to save high scores in");
+            return;
+        };
+
+        let result = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::write(&path, self.to_text()));
+
+        if let Err(error) = result {
+            warn!("could not save high scores to {}: {error}", path.display());
+        }
+    }
+
+    /// The high scores of `mode`, best first.
+    pub fn of_mode(&self, mode: GameMode) -> Vec<&HighScore> {
+        let mut entries: Vec<&HighScore> =
+            self.0.iter().filter(|entry| entry.mode == mode).collect();
+        entries.sort_by(|a, b| a.rank(b));
+        entries
+    }
+
+    /// Whether `entry` would make it into the table of its mode.
+    pub fn qualifies(&self, entry: &HighScore) -> bool {
+        let entries = self.of_mode(entry.mode);
+        entries.len() < HIGH_SCORES_KEPT
+            || entries
+                .last()
+                .is_some_and(|last| entry.rank(last) == Ordering::Less)
+    }
+
+    /// Adds `entry`, dropping whichever game of its mode falls off the
+    /// bottom of the table.
+    pub fn insert(&mut self, entry: HighScore) {
+        // games only rank against others of the same mode, so each table
+        // is sorted on its own
+        let (mut table, others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition(|other| other.mode == entry.mode);
+
+        table.push(entry);
+        table.sort_by(|a, b| a.rank(b));
+        table.truncate(HIGH_SCORES_KEPT);
+
+        self.0 = others;
+        self.0.extend(table);
+    }
+
+    /// The name of whoever set the most recent high score.
+    pub fn last_name(&self) -> Option<&str> {
+        self.0
+            .iter()
+            .max_by_key(|entry| entry.date)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// One line per game, with tab separated fields after a header line.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{HEADER} {HIGH_SCORES_VERSION}\n");
+
+        for entry in &self.0 {
+            text += &format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                mode_key(entry.mode),
+                entry.score,
+                entry.lines,
+                entry.time.as_millis(),
+                entry.date,
+                entry.seed,
+                entry.name,
+            );
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, HighScoresError> {
+        let mut lines = text.lines();
+
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(HEADER))
+            .and_then(|version| version.trim().parse().ok())
+            .ok_or(HighScoresError::NotHighScores)?;
+
+        if version != HIGH_SCORES_VERSION {
+            return Err(HighScoresError::UnsupportedVersion(version));
+        }
+
+        let mut high_scores = HighScores::default();
+
+        for (index, line) in lines.enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // the header is line 1
+            let entry = parse_entry(line).ok_or(HighScoresError::Invalid(index + 2))?;
+            high_scores.0.push(entry);
+        }
+
+        Ok(high_scores)
+    }
+}
+
+fn parse_entry(line: &str) -> Option<HighScore> {
+    let mut fields = line.splitn(7, '\t');
+
+    Some(HighScore {
+        mode: parse_mode(fields.next()?)?,
+        score: fields.next()?.parse().ok()?,
+        lines: fields.next()?.parse().ok()?,
+        time: Duration::from_millis(fields.next()?.parse().ok()?),
+        date: fields.next()?.parse().ok()?,
+        seed: fields.next()?.parse().ok()?,
+        name: fields.next()?.to_string(),
+    })
+}
+
+/// Names a mode along with its settings, so that a 40 line sprint and a
+/// 20 line sprint keep separate tables.
+fn mode_key(mode: GameMode) -> String {
+    match mode {
+        GameMode::Marathon => "marathon".to_string(),
+        GameMode::Sprint { lines } => format!("sprint:{lines}"),
+        GameMode::Ultra { duration } => format!("ultra:{}", duration.as_millis()),
+        GameMode::Dig {
+            rows,
+            infinite: false,
+        } => format!("dig:{rows}"),
+        GameMode::Dig {
+            rows,
+            infinite: true,
+        } => format!("infinite-dig:{rows}"),
+        GameMode::Training { restart } => format!("training:{}", restart as u8),
+    }
+}
+
+fn parse_mode(key: &str) -> Option<GameMode> {
+    let (name, value) = key.split_once(':').unwrap_or((key, ""));
+
+    Some(match name {
+        "marathon" => GameMode::Marathon,
+        "sprint" => GameMode::Sprint {
+            lines: value.parse().ok()?,
+        },
+        "ultra" => GameMode::Ultra {
+            duration: Duration::from_millis(value.parse().ok()?),
+        },
+        "dig" => GameMode::Dig {
+            rows: value.parse().ok()?,
+            infinite: false,
+        },
+        "infinite-dig" => GameMode::Dig {
+            rows: value.parse().ok()?,
+            infinite: true,
+        },
+        "training" => GameMode::Training {
+            restart: match value {
+                "0" => false,
+                "1" => true,
+                _ => return None,
+            },
+        },
+        _ => return None,
+    })
+}
+
+/// Formats seconds since the Unix epoch as a `yyyy-mm-dd` date in UTC.
+pub fn format_date(date: u64) -> String {
+    // days to a civil date, after Howard Hinnant's algorithm
+    let days = (date / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mode: GameMode, score: u32, time: u64) -> HighScore {
+        HighScore {
+            mode,
+            score,
+            lines: 40,
+            time: Duration::from_millis(time),
+            date: 1_700_000_000,
+            seed: 42,
+            name: "AAA".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut high_scores = HighScores::default();
+        for mode in GameMode::ALL {
+            high_scores.insert(entry(mode, 1000, 60_000));
+        }
+
+        let text = high_scores.to_text();
+        assert_eq!(HighScores::from_text(&text), Ok(high_scores));
+    }
+
+    #[test]
+    fn keeps_the_best_of_each_mode() {
+        let sprint = GameMode::Sprint { lines: 40 };
+        let mut high_scores = HighScores::default();
+
+        for i in 0..HIGH_SCORES_KEPT as u64 + 2 {
+            high_scores.insert(entry(sprint, 0, 60_000 + i));
+            high_scores.insert(entry(GameMode::Marathon, i as u32, 60_000));
+        }
+
+        let sprints = high_scores.of_mode(sprint);
+        assert_eq!(sprints.len(), HIGH_SCORES_KEPT);
+        assert_eq!(sprints[0].time, Duration::from_millis(60_000));
+
+        let marathons = high_scores.of_mode(GameMode::Marathon);
+        assert_eq!(marathons.len(), HIGH_SCORES_KEPT);
+        assert_eq!(marathons[0].score, HIGH_SCORES_KEPT as u32 + 1);
+
+        assert!(!high_scores.qualifies(&entry(GameMode::Marathon, 1, 60_000)));
+        assert!(high_scores.qualifies(&entry(sprint, 0, 1)));
+    }
+
+    #[test]
+    fn keeps_each_mode_apart() {
+        let sprint = GameMode::Sprint { lines: 40 };
+        let ultra = GameMode::Ultra {
+            duration: Duration::from_secs(120),
+        };
+        let mut high_scores = HighScores::default();
+
+        let games = HIGH_SCORES_KEPT as u32 * 3;
+        let score = |i: u32| (i * 7919) % 101;
+        let time = |i: u32| 60_000 + u64::from((i * 104_729) % 97);
+
+        // races and scored modes rank differently, and mustn't be compared
+        for i in 0..games {
+            let (score, time) = (score(i), time(i));
+            high_scores.insert(entry(sprint, score, time));
+            high_scores.insert(entry(GameMode::Marathon, score, time));
+            high_scores.insert(entry(ultra, score, time));
+        }
+
+        for mode in [sprint, GameMode::Marathon, ultra] {
+            let entries = high_scores.of_mode(mode);
+            assert_eq!(entries.len(), HIGH_SCORES_KEPT, "{mode:?}");
+            assert!(entries.iter().all(|entry| entry.mode == mode));
+        }
+
+        let mut best_times: Vec<_> = (0..games).map(time).collect();
+        best_times.sort();
+        let sprint_times: Vec<_> = high_scores
+            .of_mode(sprint)
+            .iter()
+            .map(|entry| entry.time.as_millis() as u64)
+            .collect();
+        assert_eq!(sprint_times, best_times[..HIGH_SCORES_KEPT]);
+
+        let mut best_scores: Vec<_> = (0..games).map(score).collect();
+        best_scores.sort_by(|a, b| b.cmp(a));
+        let marathon_scores: Vec<_> = high_scores
+            .of_mode(GameMode::Marathon)
+            .iter()
+            .map(|entry| entry.score)
+            .collect();
+        assert_eq!(marathon_scores, best_scores[..HIGH_SCORES_KEPT]);
+        assert_eq!(high_scores.0.len(), 3 * HIGH_SCORES_KEPT);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(
+            HighScores::from_text("hello"),
+            Err(HighScoresError::NotHighScores)
+        );
+        assert_eq!(
+            HighScores::from_text("tetris-rust high scores 2\n"),
+            Err(HighScoresError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            HighScores::from_text("tetris-rust high scores 1\nmarathon\t1\n"),
+            Err(HighScoresError::Invalid(2))
+        );
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_700_000_000), "2023-11-14");
+    }
+}
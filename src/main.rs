@@ -1,157 +1,126 @@
+use bevy::ecs::spawn::SpawnIter;
 use bevy::prelude::*;
-use bevy::window::WindowResolution;
+use bevy::window::WindowFocused;
 
 use catppuccin::ColorName;
-use rand::distr::StandardUniform;
 use rand::prelude::*;
-
-use std::collections::{HashMap, VecDeque};
-
-const BLOCK_SIZE: f32 = 32.0;
-
-const GRID_HEIGHT: i32 = 22;
-const GRID_WIDTH: i32 = 10;
-
-const TETROMINOS: [TetrominoKind; 7] = [
-    TetrominoKind::I,
-    TetrominoKind::O,
-    TetrominoKind::T,
-    TetrominoKind::S,
-    TetrominoKind::Z,
-    TetrominoKind::J,
-    TetrominoKind::L,
-];
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum Rotation {
-    North,
-    South,
-    West,
-    East,
-}
-
-#[derive(Resource, Default)]
-struct Score(u32);
+use serde::{Deserialize, Serialize};
+
+mod actions;
+mod effects;
+mod high_scores;
+mod menu;
+mod replay;
+mod settings;
+mod stats;
+mod trainer;
+
+use actions::{Action, Actions, ActionsPlugin};
+use effects::EffectsPlugin;
+use menu::{Menu, MenuPlugin, menu_background, typing_in_field};
+use replay::{Recording, ReplayPlugin};
+use settings::{Settings, SettingsPlugin};
+use stats::{StatsPlugin, spawn_stats_panel};
+use tetris_rust::engine::{
+    self, Clear, End, Engine, GRID_HEIGHT, GRID_WIDTH, Handling, Held, Input, Replay, Ruleset,
+    Spin, TETROMINOS, Tetromino, TetrominoKind,
+};
+use trainer::{TrainerPlugin, spawn_trainer_text};
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How big the tiles of the hold and preview slots are next to those of the
+/// board.
+const SLOT_SCALE: f32 = 0.75;
 
 #[derive(Component)]
 struct ScoreText;
 
-#[rustfmt::skip]
-#[allow(clippy::type_complexity)]
-const TETROMINO_SHAPES: [(TetrominoKind, [(Rotation, [IVec2; 4]); 4]); 7] = [
-    (
-        TetrominoKind::I,
-        [
-            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(2, 0)]),
-            (Rotation::East,  [ivec2(1, 1), ivec2(1, 0), ivec2(1, -1), ivec2(1, -2)]),
-            (Rotation::South, [ivec2(-1, -1), ivec2(0, -1), ivec2(1, -1), ivec2(2, -1)]),
-            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(0, -2)]),
-        ],
-    ),
-    (
-        TetrominoKind::O,
-        [
-            (Rotation::North, [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::East,  [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::South, [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::West,  [ivec2(0, 0), ivec2(1, 0), ivec2(0, -1), ivec2(1, -1)]),
-        ],
-    ),
-    (
-        TetrominoKind::T,
-        [
-            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(0, 1)]),
-            (Rotation::East,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(1, 0)]),
-            (Rotation::South, [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(0, -1)]),
-            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(-1, 0)]),
-        ],
-    ),
-    (
-        TetrominoKind::S,
-        [
-            (Rotation::North, [ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1), ivec2(0, -1)]),
-            (Rotation::East,  [ivec2(0, 1), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
-            (Rotation::South, [ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1), ivec2(0, -1)]),
-            (Rotation::West,  [ivec2(0, 1), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
-        ],
-    ),
-    (
-        TetrominoKind::Z,
-        [
-            (Rotation::North, [ivec2(-1, 0), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::East,  [ivec2(1, 1), ivec2(1, 0), ivec2(0, 0), ivec2(0, -1)]),
-            (Rotation::South, [ivec2(-1, 0), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::West,  [ivec2(1, 1), ivec2(1, 0), ivec2(0, 0), ivec2(0, -1)]),
-        ],
-    ),
-    (
-        TetrominoKind::J,
-        [
-            (Rotation::North, [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(-1, -1)]),
-            (Rotation::East,  [ivec2(-1, 1), ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0)]),
-            (Rotation::South, [ivec2(1, 1), ivec2(0, 1), ivec2(0, 0), ivec2(0, -1)]),
-            (Rotation::West,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(1, -1)]),
-        ],
-    ),
-    (
-        TetrominoKind::L,
-        [
-            (Rotation::North, [ivec2(0, 1), ivec2(0, 0), ivec2(0, -1), ivec2(1, -1)]),
-            (Rotation::East,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(-1, -1)]),
-            (Rotation::South, [ivec2(-1, 1), ivec2(0, 1), ivec2(0, 0), ivec2(0, -1)]),
-            (Rotation::West,  [ivec2(-1, 0), ivec2(0, 0), ivec2(1, 0), ivec2(1, 1)]),
-        ],
-    ),
-];
-
 fn main() {
     App::new()
-        .add_event::<Tick>()
+        .add_event::<EngineEvent>()
         .add_event::<ThemeSwitched>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
-                resolution: WindowResolution::new(1920.0, 1280.0),
                 title: "Tetris but Rust".into(),
                 ..default()
             }),
             ..default()
         }))
+        // the settings size the window, so they come after the window plugin
+        .add_plugins((
+            ActionsPlugin,
+            EffectsPlugin,
+            MenuPlugin,
+            ReplayPlugin,
+            SettingsPlugin,
+            StatsPlugin,
+            TrainerPlugin,
+        ))
         .init_resource::<Theme>()
         .init_resource::<Random>()
-        .insert_resource(PieceQueue::new(&mut rand::rng()))
+        .init_resource::<SeedChoice>()
+        .init_resource::<Rules>()
+        .init_resource::<PlayerHandling>()
+        .init_resource::<PreviewCount>()
         .init_state::<GameState>()
         .add_systems(Startup, setup)
-        .add_systems(Update, (toggle_theme, recolor))
-        .add_systems(OnEnter(GameState::Running), setup_game)
         .add_systems(
-            FixedUpdate,
-            (send_tick, gravity, clear_lines)
+            Update,
+            (
+                toggle_theme.run_if(not(typing_in_field)),
+                recolor,
+                draw_piece_slots,
+                fade_callouts,
+                update_instructions,
+            ),
+        )
+        // resuming from the pause menu carries on with the same game
+        .add_systems(
+            OnEnter(GameState::Running),
+            (despawn_all::<InGame>, despawn_all::<Redraw>, setup_game)
                 .chain()
-                .run_if(in_state(GameState::Running)),
+                .run_if(not(resource_exists::<Game>)),
+        )
+        .add_systems(
+            FixedUpdate,
+            advance_game.run_if(in_state(GameState::Running)),
         )
         .add_systems(
             Update,
             (
-                update_sprites,
                 handle_movement,
                 handle_rotation,
-                ghost_piece,
-                update_score_text,
+                handle_hold,
+                handle_engine_events,
                 toggle_instructions,
+                pause_on_focus_loss,
             )
                 .run_if(in_state(GameState::Running)),
         )
-        .add_systems(OnExit(GameState::GameOver), despawn_all::<Tetromino>)
-        .add_systems(OnEnter(GameState::GameOver), setup_game_over_screen)
         .add_systems(
             Update,
-            button_interaction.run_if(in_state(GameState::GameOver)),
+            handle_pause.run_if(in_state(GameState::Running).or(in_state(GameState::Paused))),
+        )
+        // replays show the game the same way it is played
+        .add_systems(
+            Update,
+            (
+                update_sprites,
+                spawn_callouts,
+                update_hold_slot,
+                update_preview_slots,
+                update_score_text,
+                update_level_text,
+                update_time_text,
+            )
+                .run_if(in_state(GameState::Running).or(in_state(GameState::Replay))),
         )
-        .add_systems(OnExit(GameState::GameOver), despawn_all::<GameOverScreen>)
         .run();
 }
 
-#[derive(Resource, Default, Clone, Copy)]
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
 enum Theme {
     #[default]
     Dark,
@@ -159,6 +128,20 @@ enum Theme {
 }
 
 impl Theme {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+
+    fn toggle(&mut self) {
+        *self = match *self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::Dark,
+        };
+    }
+
     pub fn color(&self, name: catppuccin::ColorName) -> Color {
         let palette = match self {
             Theme::Dark => catppuccin::PALETTE.mocha.colors,
@@ -175,16 +158,12 @@ impl Theme {
 }
 
 fn toggle_theme(
-    input: Res<ButtonInput<KeyCode>>,
+    actions: Actions,
     mut theme: ResMut<Theme>,
     mut events: EventWriter<ThemeSwitched>,
 ) {
-    if input.just_pressed(KeyCode::KeyL) {
-        *theme = match *theme {
-            Theme::Dark => Theme::Light,
-            Theme::Light => Theme::Dark,
-        };
-
+    if actions.just_pressed(Action::ToggleTheme) {
+        theme.toggle();
         events.write_default();
     }
 }
@@ -197,6 +176,10 @@ fn recolor(
     mut clear_color: ResMut<ClearColor>,
     instructions: Query<&Children, With<Instructions>>,
     score_display: Query<&Children, With<ScoreDisplay>>,
+    hold_panel: Query<&Children, With<HoldPanel>>,
+    preview_panel: Query<&Children, With<PreviewPanel>>,
+    mut menus: Query<(&Children, &mut BackgroundColor), With<Menu>>,
+    buttons: Query<&Children, With<Button>>,
     mut text_colors: Query<&mut TextColor>,
 ) {
     for _ in events.read() {
@@ -221,20 +204,57 @@ fn recolor(
                 }
             }
         }
+
+        if let Ok(hold_panel) = hold_panel.single() {
+            for &child in hold_panel {
+                if let Ok(mut font) = text_colors.get_mut(child) {
+                    *font = TextColor(theme.color(ColorName::Text))
+                }
+            }
+        }
+
+        if let Ok(preview_panel) = preview_panel.single() {
+            for &child in preview_panel {
+                if let Ok(mut font) = text_colors.get_mut(child) {
+                    *font = TextColor(theme.color(ColorName::Text))
+                }
+            }
+        }
+
+        for (children, mut background) in &mut menus {
+            *background = menu_background(&theme);
+
+            for &child in children {
+                if let Ok(mut font) = text_colors.get_mut(child) {
+                    *font = TextColor(theme.color(ColorName::Text))
+                }
+            }
+        }
+
+        for children in &buttons {
+            for &child in children {
+                if let Ok(mut font) = text_colors.get_mut(child) {
+                    *font = TextColor(theme.color(ColorName::Text))
+                }
+            }
+        }
     }
 }
 
 #[derive(Event, Default)]
 struct ThemeSwitched;
 
+/// Picks the seeds of games the player didn't choose a seed for.
 #[derive(Resource)]
-struct Ticker(Timer);
+struct Random(StdRng);
 
-#[derive(Component)]
-struct Active;
+/// The seed the player chose for their next games, or `None` for a random
+/// one each game.
+#[derive(Resource, Default)]
+struct SeedChoice(Option<u64>);
 
-#[derive(Resource)]
-struct Random(StdRng);
+#[derive(Resource, Default)]
+struct Rules(Ruleset);
 
 impl Default for Random {
     fn default() -> Self {
@@ -242,156 +262,29 @@ impl Default for Random {
     }
 }
 
-#[derive(Resource)]
-struct PieceQueue(VecDeque<TetrominoKind>);
+/// Forwards the [`engine::Event`]s returned by the engine to Bevy systems.
+#[derive(Event)]
+struct EngineEvent(engine::Event);
 
-impl PieceQueue {
-    fn new<R>(rng: &mut R) -> Self
-    where
-        R: Rng + ?Sized,
-    {
-        let mut result = TETROMINOS;
-        result.shuffle(rng);
-        PieceQueue(VecDeque::from_iter(result))
-    }
-
-    fn next<R>(&mut self, rng: &mut R) -> TetrominoKind
-    where
-        R: Rng + ?Sized,
-    {
-        self.0.push_back(rng.random::<TetrominoKind>());
-        self.0.pop_front().unwrap_or_else(|| self.next(rng))
-    }
-}
+#[derive(Resource)]
+struct Game(Engine);
 
+/// Inputs pressed since the last fixed update, applied at the start of the
+/// next one.
 #[derive(Resource, Default)]
-struct Grid {
-    tiles: HashMap<IVec2, catppuccin::ColorName>,
-}
+struct PendingInputs(Vec<Input>);
 
-#[derive(Event, Default)]
-struct Tick;
-
-#[derive(Component, Clone)]
-struct Tetromino {
-    position: IVec2,
-    kind: TetrominoKind,
-    rotation: Rotation,
-    is_ghost: bool,
-}
-
-impl Tetromino {
-    fn new(kind: TetrominoKind) -> Self {
-        Tetromino {
-            position: IVec2::new(GRID_WIDTH / 2, GRID_HEIGHT),
-            kind,
-            rotation: Rotation::North,
-            is_ghost: false,
-        }
-    }
+#[derive(Resource, Default, PartialEq)]
+struct PlayerHandling(Handling);
 
-    const fn color(&self) -> ColorName {
-        if !self.is_ghost {
-            self.kind.color()
-        } else {
-            ColorName::Overlay0
-        }
-    }
-
-    fn move_left(&mut self) {
-        self.position.x -= 1;
-    }
-
-    fn move_right(&mut self) {
-        self.position.x += 1;
-    }
-
-    fn move_up(&mut self) {
-        self.position.y += 1;
-    }
-
-    fn move_down(&mut self) {
-        self.position.y -= 1;
-    }
-
-    fn rotate_left(&mut self) {
-        self.rotation = match self.rotation {
-            Rotation::North => Rotation::West,
-            Rotation::West => Rotation::South,
-            Rotation::South => Rotation::East,
-            Rotation::East => Rotation::North,
-        }
-    }
-
-    fn rotate_right(&mut self) {
-        self.rotation = match self.rotation {
-            Rotation::North => Rotation::East,
-            Rotation::East => Rotation::South,
-            Rotation::South => Rotation::West,
-            Rotation::West => Rotation::North,
-        }
-    }
-
-    fn occupied_tiles(&self) -> [IVec2; 4] {
-        for (kind, rest) in TETROMINO_SHAPES {
-            if kind != self.kind {
-                continue;
-            }
-
-            for (rotation, tiles) in rest {
-                if rotation != self.rotation {
-                    continue;
-                }
-
-                return tiles.map(|position| position + self.position);
-            }
-        }
-
-        unreachable!()
-    }
-
-    fn is_in_ground(&self) -> bool {
-        self.occupied_tiles().iter().any(|&tile_pos| tile_pos.y < 0)
-    }
-
-    fn is_in_wall(&self) -> bool {
-        self.occupied_tiles()
-            .iter()
-            .any(|&tile_pos| tile_pos.x < 0 || tile_pos.x >= GRID_WIDTH)
-    }
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum TetrominoKind {
-    I,
-    O,
-    T,
-    S,
-    Z,
-    J,
-    L,
-}
-
-impl Distribution<TetrominoKind> for StandardUniform {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> TetrominoKind {
-        *TETROMINOS.choose(rng).unwrap()
-    }
-}
-
-impl TetrominoKind {
-    const fn color(&self) -> ColorName {
-        use ColorName::*;
+/// Everything spawned for a game, which is despawned before the next one
+/// starts.
+#[derive(Component)]
+struct InGame;
 
-        match *self {
-            TetrominoKind::I => Teal,
-            TetrominoKind::O => Yellow,
-            TetrominoKind::T => Mauve,
-            TetrominoKind::S => Green,
-            TetrominoKind::Z => Red,
-            TetrominoKind::J => Peach,
-            TetrominoKind::L => Blue,
-        }
-    }
+/// Where the game keeps its files, such as replays and high scores.
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("tetris-rust"))
 }
 
 #[derive(Component)]
@@ -400,49 +293,79 @@ struct BackgroundCell;
 #[derive(Component)]
 struct Instructions;
 
-fn setup(mut commands: Commands) {
+/// A line of the instructions, which lists the keys bound to this action.
+#[derive(Component)]
+struct InstructionLine(Action);
+
+fn setup(mut commands: Commands, theme: Res<Theme>) {
     commands.spawn(Camera2d);
+    commands.insert_resource(ClearColor(theme.color(ColorName::Base)));
 }
 
 #[derive(Component)]
 struct ScoreDisplay;
 
+#[allow(clippy::too_many_arguments)]
 fn setup_game(
     mut commands: Commands,
-    mut event_writer: EventWriter<Tick>,
     asset_server: Res<AssetServer>,
     theme: Res<Theme>,
-    mut piece_queue: ResMut<PieceQueue>,
     mut rng: ResMut<Random>,
+    seed_choice: Res<SeedChoice>,
+    rules: Res<Rules>,
+    handling: Res<PlayerHandling>,
+    settings: Res<Settings>,
+    time: Res<Time<Fixed>>,
 ) {
-    commands.insert_resource(Ticker(Timer::from_seconds(0.5, TimerMode::Repeating)));
-    commands.insert_resource(Grid::default());
-    commands.insert_resource(Score::default());
-
-    commands.spawn((Tetromino::new(piece_queue.next(&mut rng.0)), Active));
+    let seed = seed_choice.0.unwrap_or_else(|| rng.0.random());
+    let mut engine = Engine::new(rules.0, seed);
+    engine.set_handling(handling.0);
+    commands.insert_resource(Game(engine));
+    commands.insert_resource(PendingInputs::default());
+    commands.insert_resource(Recording(Replay::new(
+        seed,
+        rules.0,
+        handling.0,
+        time.timestep(),
+    )));
+
+    spawn_game_ui(
+        &mut commands,
+        &asset_server,
+        &theme,
+        settings.block_size,
+        seed,
+    );
+}
 
-    commands.insert_resource(ClearColor(theme.color(ColorName::Base)));
+/// Spawns the board, the side panels and the instructions around a game
+/// that started from `seed`, with tiles of `block_size` pixels.
+fn spawn_game_ui(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    theme: &Theme,
+    block_size: f32,
+    seed: u64,
+) {
     for x in 0..GRID_WIDTH {
         for y in 0..GRID_HEIGHT {
             commands.spawn((
+                InGame,
                 BackgroundCell,
                 Sprite {
                     color: theme.color(ColorName::Surface1),
-                    custom_size: Some(Vec2::splat(BLOCK_SIZE - 1.0)),
+                    custom_size: Some(Vec2::splat(block_size - 1.0)),
                     ..default()
                 },
                 Transform::from_xyz(
-                    x as f32 * BLOCK_SIZE - GRID_WIDTH as f32 / 2.0 * BLOCK_SIZE,
-                    y as f32 * BLOCK_SIZE - GRID_HEIGHT as f32 / 2.0 * BLOCK_SIZE,
+                    x as f32 * block_size - GRID_WIDTH as f32 / 2.0 * block_size,
+                    y as f32 * block_size - GRID_HEIGHT as f32 / 2.0 * block_size,
                     -2.0,
                 ),
             ));
         }
     }
 
-    // Initial tick
-    event_writer.write_default();
-
     let font = asset_server.load("fonts/Roboto-Regular.ttf");
     let score_font = (
         TextFont {
@@ -454,6 +377,7 @@ fn setup_game(
     );
 
     commands.spawn((
+        InGame,
         ScoreDisplay,
         Node {
             padding: UiRect::all(Val::Px(32.0)),
@@ -465,6 +389,9 @@ fn setup_game(
         ],
     ));
 
+    spawn_stats_panel(commands, &font, theme);
+    spawn_trainer_text(commands, &font, theme);
+
     let instruction_font = (
         TextFont {
             font: font.clone(),
@@ -475,6 +402,7 @@ fn setup_game(
     );
 
     commands.spawn((
+        InGame,
         Instructions,
         Visibility::Visible,
         Node {
@@ -486,120 +414,370 @@ fn setup_game(
             margin: UiRect::all(Val::Px(8.0)),
             ..default()
         },
-        children![
-            (Text::new("Use A and D to move"), instruction_font.clone()),
-            (Text::new("Use Q and E to rotate"), instruction_font.clone()),
-            (Text::new("Use S to soft drop"), instruction_font.clone()),
-            (
-                Text::new("Use L to switch between light/dark mode"),
-                instruction_font.clone()
-            ),
+        Children::spawn(SpawnIter(Action::ALL.into_iter().map(move |action| {
             (
-                Text::new("Use TAB to toggle instructions"),
-                instruction_font.clone()
-            ),
-        ],
+                InstructionLine(action),
+                Text::default(),
+                instruction_font.clone(),
+            )
+        }))),
     ));
-}
-
-#[derive(Component)]
-struct GameOverScreen;
 
-fn setup_game_over_screen(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    theme: Res<Theme>,
-) {
-    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+    let panel_font = (
+        TextFont {
+            font: font.clone(),
+            font_size: 32.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Text)),
+    );
 
+    // Lays the panels out on either side of the board, which is drawn with
+    // sprites around the world origin.
     commands.spawn((
-        GameOverScreen,
+        InGame,
         Node {
+            position_type: PositionType::Absolute,
             width: Val::Percent(100.0),
             height: Val::Percent(100.0),
-            flex_direction: FlexDirection::Column,
+            left: Val::Px(-block_size / 2.0),
+            top: Val::Px(block_size / 2.0),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
-            margin: UiRect::all(Val::Px(8.0)),
             ..default()
         },
         children![
             (
-                Text::new("Game Over"),
-                TextFont {
-                    font: font.clone(),
-                    font_size: 80.0,
+                HoldPanel,
+                Node {
+                    width: Val::Px(5.0 * block_size),
+                    height: Val::Px(GRID_HEIGHT as f32 * block_size),
+                    margin: UiRect::right(Val::Px(block_size)),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
                     ..default()
                 },
-                TextColor(theme.color(ColorName::Text)),
-                TextShadow::default(),
+                children![
+                    (Text::new("Hold"), panel_font.clone()),
+                    (HoldSlot, PieceSlot::default(), piece_slot_node(block_size)),
+                    (
+                        Text::new("Level"),
+                        panel_font.clone(),
+                        Node {
+                            margin: UiRect::top(Val::Px(2.0 * block_size)),
+                            ..default()
+                        },
+                    ),
+                    (Text::default(), panel_font.clone(), LevelText),
+                    (
+                        Text::new("Lines"),
+                        panel_font.clone(),
+                        Node {
+                            margin: UiRect::top(Val::Px(block_size)),
+                            ..default()
+                        },
+                    ),
+                    (Text::default(), panel_font.clone(), LinesText),
+                    (
+                        Text::new("Time"),
+                        panel_font.clone(),
+                        Node {
+                            margin: UiRect::top(Val::Px(block_size)),
+                            ..default()
+                        },
+                    ),
+                    (Text::default(), panel_font.clone(), TimeText),
+                    (
+                        Text::default(),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            ..default()
+                        },
+                        TextColor(theme.color(ColorName::Subtext1)),
+                        SplitsText,
+                    ),
+                    (
+                        Text::new(format!("Seed: {seed}")),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            ..default()
+                        },
+                        TextColor(theme.color(ColorName::Subtext1)),
+                        Node {
+                            margin: UiRect::top(Val::Px(block_size)),
+                            ..default()
+                        },
+                    ),
+                    (
+                        Callouts,
+                        Node {
+                            margin: UiRect::top(Val::Px(2.0 * block_size)),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                    ),
+                ],
             ),
+            Node {
+                width: Val::Px(GRID_WIDTH as f32 * block_size),
+                ..default()
+            },
             (
-                Button,
+                PreviewPanel,
                 Node {
-                    width: Val::Px(150.0),
-                    height: Val::Px(65.0),
-                    border: UiRect::all(Val::Px(6.0)),
-                    // horizontally center child text
-                    justify_content: JustifyContent::Center,
-                    // vertically center child text
+                    width: Val::Px(5.0 * block_size),
+                    height: Val::Px(GRID_HEIGHT as f32 * block_size),
+                    margin: UiRect::left(Val::Px(block_size)),
+                    flex_direction: FlexDirection::Column,
                     align_items: AlignItems::Center,
                     ..default()
                 },
-                BorderColor(Color::BLACK),
-                BorderRadius::all(Val::Px(16.0)),
-                children![(
-                    Text::new("Restart"),
-                    TextFont {
-                        font: font.clone(),
-                        font_size: 36.0,
-                        ..default()
-                    },
-                    TextColor(theme.color(ColorName::Text)),
-                    TextShadow::default(),
-                )]
-            )
+                Children::spawn((
+                    Spawn((Text::new("Next"), panel_font.clone())),
+                    SpawnIter((0..MAX_PREVIEW).map(move |index| (
+                        PreviewSlot(index),
+                        PieceSlot::default(),
+                        piece_slot_node(block_size)
+                    )),),
+                )),
+            ),
         ],
     ));
 }
 
-fn despawn_all<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
-    for entity in query {
-        commands.entity(entity).despawn();
+/// How many upcoming pieces the preview panel shows, between 1 and
+/// [`MAX_PREVIEW`].
+#[derive(Resource, PartialEq)]
+struct PreviewCount(usize);
+
+impl Default for PreviewCount {
+    fn default() -> Self {
+        Self(5)
     }
 }
 
-#[allow(clippy::type_complexity)]
-fn button_interaction(
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &mut BorderColor),
-        (Changed<Interaction>, With<Button>),
-    >,
-    mut game_state: ResMut<NextState<GameState>>,
+const MAX_PREVIEW: usize = TETROMINOS.len();
+
+#[derive(Component)]
+struct PreviewPanel;
+
+/// Shows the upcoming piece at this index of the queue.
+#[derive(Component)]
+struct PreviewSlot(usize);
+
+#[derive(Component)]
+struct HoldPanel;
+
+#[derive(Component)]
+struct HoldSlot;
+
+/// A UI box that draws a single piece outside of the board.
+#[derive(Component, Default, PartialEq)]
+struct PieceSlot {
+    kind: Option<TetrominoKind>,
+    dimmed: bool,
+}
+
+fn piece_slot_node(block_size: f32) -> Node {
+    let slot_block_size = block_size * SLOT_SCALE;
+
+    Node {
+        width: Val::Px(4.0 * slot_block_size),
+        height: Val::Px(3.0 * slot_block_size),
+        margin: UiRect::top(Val::Px(8.0)),
+        ..default()
+    }
+}
+
+fn draw_piece_slots(
+    mut commands: Commands,
+    slots: Query<(Entity, Ref<PieceSlot>)>,
+    mut events: EventReader<ThemeSwitched>,
     theme: Res<Theme>,
+    settings: Res<Settings>,
 ) {
-    for (interaction, mut color, mut border_color) in &mut interaction_query {
-        match *interaction {
-            Interaction::Hovered => {
-                *color = theme.color(ColorName::Surface2).into();
-                border_color.0 = Color::WHITE;
-            }
-            Interaction::None => {
-                *color = theme.color(ColorName::Subtext1).into();
-                border_color.0 = Color::BLACK;
-            }
-            Interaction::Pressed => {
-                game_state.set(GameState::Running);
+    let theme_switched = events.read().count() > 0;
+    let slot_block_size = settings.block_size * SLOT_SCALE;
+
+    for (entity, slot) in &slots {
+        if !slot.is_changed() && !theme_switched {
+            continue;
+        }
+
+        commands.entity(entity).despawn_related::<Children>();
+
+        let Some(kind) = slot.kind else {
+            continue;
+        };
+
+        let color = theme.color(if slot.dimmed {
+            ColorName::Overlay0
+        } else {
+            kind.color()
+        });
+
+        let tiles = Tetromino {
+            position: IVec2::ZERO,
+            ..Tetromino::new(kind)
+        }
+        .occupied_tiles();
+
+        let min = tiles.into_iter().reduce(IVec2::min).unwrap();
+        let max = tiles.into_iter().reduce(IVec2::max).unwrap();
+        // center the piece within the 4x3 slot
+        let offset = (vec2(4.0, 3.0) - (max - min + 1).as_vec2()) / 2.0 * slot_block_size;
+
+        for tile in tiles {
+            commands.spawn((
+                Node {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(offset.x + (tile.x - min.x) as f32 * slot_block_size),
+                    top: Val::Px(offset.y + (max.y - tile.y) as f32 * slot_block_size),
+                    width: Val::Px(slot_block_size - 1.0),
+                    height: Val::Px(slot_block_size - 1.0),
+                    ..default()
+                },
+                BackgroundColor(color),
+                ChildOf(entity),
+            ));
+        }
+    }
+}
+
+fn update_preview_slots(
+    game: Res<Game>,
+    preview_count: Res<PreviewCount>,
+    mut slots: Query<(&mut PieceSlot, &PreviewSlot)>,
+) {
+    let count = preview_count.0.clamp(1, MAX_PREVIEW);
+
+    for (mut slot, &PreviewSlot(index)) in &mut slots {
+        slot.set_if_neq(PieceSlot {
+            kind: game
+                .0
+                .queue()
+                .upcoming
+                .get(index)
+                .copied()
+                .filter(|_| index < count),
+            dimmed: false,
+        });
+    }
+}
+
+fn update_hold_slot(game: Res<Game>, mut slots: Query<&mut PieceSlot, With<HoldSlot>>) {
+    for mut slot in &mut slots {
+        slot.set_if_neq(PieceSlot {
+            kind: game.0.hold(),
+            dimmed: !game.0.can_hold(),
+        });
+    }
+}
+
+/// Stacks the callouts for the bonuses scored by recent clears.
+#[derive(Component)]
+struct Callouts;
+
+/// A line of text that fades out and disappears once its timer runs out.
+#[derive(Component)]
+struct Callout(Timer);
+
+const CALLOUT_DURATION: Duration = Duration::from_millis(1500);
+
+fn spawn_callouts(
+    mut commands: Commands,
+    mut events: EventReader<EngineEvent>,
+    callouts: Query<Entity, With<Callouts>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let Ok(callouts) = callouts.single() else {
+        return;
+    };
+
+    for EngineEvent(event) in events.read() {
+        let (text, color) = match *event {
+            engine::Event::Cleared(clear) if clear.is_difficult() => {
+                (clear_label(clear), ColorName::Mauve)
             }
+            engine::Event::BackToBack(_) => ("Back-to-Back".to_string(), ColorName::Peach),
+            engine::Event::Combo(combo) => (format!("{combo} Combo"), ColorName::Sky),
+            engine::Event::PerfectClear => ("All Clear".to_string(), ColorName::Yellow),
+            _ => continue,
+        };
+
+        commands.spawn((
+            Callout(Timer::new(CALLOUT_DURATION, TimerMode::Once)),
+            Text::new(text),
+            TextFont {
+                font: asset_server.load("fonts/Roboto-Regular.ttf"),
+                font_size: 28.0,
+                ..default()
+            },
+            TextColor(theme.color(color)),
+            ChildOf(callouts),
+        ));
+    }
+}
+
+fn clear_label(clear: Clear) -> String {
+    let spin = match clear.spin {
+        Spin::None => "",
+        Spin::Mini => "Mini T-Spin",
+        Spin::Full => "T-Spin",
+    };
+
+    let lines = match clear.lines {
+        0 => "",
+        1 => "Single",
+        2 => "Double",
+        3 => "Triple",
+        _ => "Tetris",
+    };
+
+    [spin, lines]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn fade_callouts(
+    mut commands: Commands,
+    mut callouts: Query<(Entity, &mut Callout, &mut TextColor)>,
+    time: Res<Time>,
+) {
+    for (entity, mut callout, mut color) in &mut callouts {
+        callout.0.tick(time.delta());
+
+        if callout.0.finished() {
+            commands.entity(entity).despawn();
+        } else {
+            color.0.set_alpha(callout.0.fraction_remaining());
         }
     }
 }
 
-fn toggle_instructions(
-    input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Visibility, With<Instructions>>,
+fn despawn_all<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn pause_on_focus_loss(
+    mut events: EventReader<WindowFocused>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    if input.just_pressed(KeyCode::Tab)
+    if events.read().any(|event| !event.focused) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+fn toggle_instructions(actions: Actions, mut query: Query<&mut Visibility, With<Instructions>>) {
+    if actions.just_pressed(Action::ToggleInstructions)
         && let Ok(mut visibility) = query.single_mut()
     {
         *visibility = match *visibility {
@@ -610,147 +788,231 @@ fn toggle_instructions(
     }
 }
 
-fn update_score_text(mut query: Query<&mut Text, With<ScoreText>>, score: Res<Score>) {
-    for mut text in &mut query {
-        **text = score.0.to_string()
+/// Keeps the instructions in line with the current bindings.
+fn update_instructions(
+    settings: Res<Settings>,
+    mut lines: Query<(Ref<InstructionLine>, &mut Text)>,
+) {
+    for (line, mut text) in &mut lines {
+        if settings.is_changed() || line.is_added() {
+            let action = line.0;
+            **text = format!("{}: {}", action.name(), settings.bindings.describe(action));
+        }
     }
 }
 
-fn send_tick(
-    mut ticker: ResMut<Ticker>,
-    time: Res<Time<Fixed>>,
-    mut event_writer: EventWriter<Tick>,
-) {
-    ticker.0.tick(time.delta());
-
-    if ticker.0.finished() {
-        event_writer.write_default();
+fn update_score_text(mut query: Query<&mut Text, With<ScoreText>>, game: Res<Game>) {
+    for mut text in &mut query {
+        **text = game.0.score().0.to_string()
     }
 }
 
-#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
-enum GameState {
-    #[default]
-    Running,
-    GameOver,
+#[derive(Component)]
+struct LevelText;
+
+#[derive(Component)]
+struct LinesText;
+
+#[derive(Component)]
+struct TimeText;
+
+/// Lists the split times, one for every [`engine::SPLIT_LINES`] lines.
+#[derive(Component)]
+struct SplitsText;
+
+/// Formats `time` as minutes, seconds and milliseconds.
+fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
 }
 
-fn gravity(
-    mut active_tetromino: Query<(&mut Tetromino, Entity), With<Active>>,
-    mut grid: ResMut<Grid>,
-    mut commands: Commands,
-    mut event_reader: EventReader<Tick>,
-    mut game_state: ResMut<NextState<GameState>>,
-    mut piece_queue: ResMut<PieceQueue>,
-    mut rng: ResMut<Random>,
+fn update_time_text(
+    mut time_text: Query<&mut Text, (With<TimeText>, Without<SplitsText>)>,
+    mut splits_text: Query<&mut Text, (With<SplitsText>, Without<TimeText>)>,
+    game: Res<Game>,
 ) {
-    for _tick in event_reader.read() {
-        if let Ok((mut tetromino, entity)) = active_tetromino.single_mut() {
-            let mut new_tetromino = tetromino.clone();
-            new_tetromino.move_down();
-            let color = tetromino.color();
-
-            let mut is_in_other_tile = false;
-            for pos in new_tetromino.occupied_tiles() {
-                if grid.tiles.contains_key(&pos) {
-                    is_in_other_tile = true;
-                    break;
-                }
-            }
+    // timed modes count down to the end of the game instead
+    let time = match game.0.ruleset().mode.time_limit() {
+        Some(limit) => limit.saturating_sub(game.0.time()),
+        None => game.0.time(),
+    };
 
-            if !new_tetromino.is_in_ground() && !is_in_other_tile {
-                *tetromino = new_tetromino;
-            } else {
-                for IVec2 { x, y } in tetromino.occupied_tiles() {
-                    if grid.tiles.insert(IVec2::new(x, y), color).is_some() {
-                        game_state.set(GameState::GameOver);
-                    }
-                }
+    for mut text in &mut time_text {
+        **text = format_time(time)
+    }
 
-                commands.entity(entity).despawn();
+    // splits are only worth showing in modes that race to a line goal
+    if game.0.ruleset().mode.goal_lines().is_none() {
+        return;
+    }
 
-                commands.spawn((Tetromino::new(piece_queue.next(&mut rng.0)), Active));
-            }
-        }
+    for mut text in &mut splits_text {
+        **text = game
+            .0
+            .splits()
+            .iter()
+            .zip(1..)
+            .map(|(&split, index)| {
+                format!("{}: {}", index * engine::SPLIT_LINES, format_time(split))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
-#[derive(Component)]
-struct Redraw;
+fn update_level_text(
+    mut level_text: Query<&mut Text, (With<LevelText>, Without<LinesText>)>,
+    mut lines_text: Query<&mut Text, (With<LinesText>, Without<LevelText>)>,
+    game: Res<Game>,
+) {
+    for mut text in &mut level_text {
+        **text = game.0.level().to_string()
+    }
 
-fn ghost_piece(
-    mut commands: Commands,
-    active_tetromino: Query<&Tetromino, With<Active>>,
-    inactive_tetromino: Query<Entity, (With<Tetromino>, Without<Active>)>,
-    grid: Res<Grid>,
+    for mut text in &mut lines_text {
+        **text = game.0.lines().to_string()
+    }
+}
+
+/// Steps the engine on the fixed timestep, so that DAS, ARR and soft drop
+/// behave the same at any frame rate.
+fn advance_game(
+    mut game: ResMut<Game>,
+    mut pending: ResMut<PendingInputs>,
+    mut recording: ResMut<Recording>,
+    actions: Actions,
+    time: Res<Time<Fixed>>,
+    mut events: EventWriter<EngineEvent>,
 ) {
-    for entity in inactive_tetromino {
-        commands.entity(entity).despawn();
+    let inputs = std::mem::take(&mut pending.0);
+    for &action in &inputs {
+        events.write_batch(game.0.apply(action).into_iter().map(EngineEvent));
     }
 
-    if let Ok(active) = active_tetromino.single().cloned() {
-        let mut ghost_tetromino = Tetromino {
-            is_ghost: true,
-            ..active
-        };
+    let held = Held {
+        left: actions.pressed(Action::MoveLeft),
+        right: actions.pressed(Action::MoveRight),
+        soft_drop: actions.pressed(Action::SoftDrop),
+    };
+    recording.0.record(&inputs, held);
 
-        while !ghost_tetromino
-            .occupied_tiles()
-            .iter()
-            .any(|tile| grid.tiles.contains_key(tile))
-            && !ghost_tetromino.is_in_ground()
-        {
-            ghost_tetromino.move_down();
-        }
+    events.write_batch(
+        game.0
+            .update(time.delta(), held)
+            .into_iter()
+            .map(EngineEvent),
+    );
+}
 
-        ghost_tetromino.move_up();
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
+enum GameState {
+    #[default]
+    MainMenu,
+    ModeSelect,
+    HighScores,
+    Running,
+    /// The game is frozen behind the pause menu.
+    Paused,
+    Settings,
+    /// Rebinding the keys of each action.
+    Controls,
+    /// The player topped out.
+    GameOver,
+    /// The game ended on the mode's own terms, such as a completed sprint or
+    /// an ultra that ran out of time.
+    Finished,
+    Replays,
+    /// A saved replay is playing back.
+    Replay,
+}
 
-        commands.spawn(ghost_tetromino);
+fn handle_engine_events(
+    mut events: EventReader<EngineEvent>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for EngineEvent(event) in events.read() {
+        match event {
+            engine::Event::GameOver(End::TopOut(_)) => game_state.set(GameState::GameOver),
+            engine::Event::GameOver(_) => game_state.set(GameState::Finished),
+            _ => {}
+        }
     }
 }
 
+#[derive(Component)]
+struct Redraw;
+
 fn update_sprites(
     mut commands: Commands,
-    tetrominoes: Query<(&Tetromino, Option<&Active>)>,
     sprites: Query<Entity, With<Redraw>>,
-    grid: Res<Grid>,
+    game: Res<Game>,
     theme: Res<Theme>,
+    settings: Res<Settings>,
 ) {
+    let block_size = settings.block_size;
+
     for sprite in sprites {
         commands.entity(sprite).despawn();
     }
 
-    for (tetromino, active) in tetrominoes {
-        let color = theme.color(tetromino.color());
+    let active = game.0.active();
+    let ghost = game.0.ghost();
+
+    // there's no piece to draw while waiting for the next one to spawn
+    let pieces = if game.0.is_between_pieces() {
+        vec![]
+    } else {
+        // render the active tetromino in front of its ghost
+        vec![
+            (active, active.color(), 1.0),
+            (&ghost, ColorName::Overlay0, 0.0),
+        ]
+    };
+
+    for (tetromino, color_name, z) in pieces {
+        let color = theme.color(color_name);
 
         for IVec2 { x, y } in tetromino.occupied_tiles() {
             commands.spawn((
                 Sprite {
                     color,
-                    custom_size: Some(Vec2::splat(BLOCK_SIZE - 1.0)),
+                    custom_size: Some(Vec2::splat(block_size - 1.0)),
                     ..default()
                 },
                 Transform::from_xyz(
-                    x as f32 * BLOCK_SIZE - GRID_WIDTH as f32 / 2.0 * BLOCK_SIZE,
-                    y as f32 * BLOCK_SIZE - GRID_HEIGHT as f32 / 2.0 * BLOCK_SIZE,
-                    // render active tetrominoes in front of inactive ones
-                    if active.is_some() { 1.0 } else { 0.0 },
+                    x as f32 * block_size - GRID_WIDTH as f32 / 2.0 * block_size,
+                    y as f32 * block_size - GRID_HEIGHT as f32 / 2.0 * block_size,
+                    z,
                 ),
                 Redraw,
             ));
         }
     }
 
-    for (&IVec2 { x, y }, &color_name) in &grid.tiles {
+    let clearing = game.0.clearing();
+
+    for (&IVec2 { x, y }, &color_name) in &game.0.grid().tiles {
+        let mut color = theme.color(color_name);
+        let mut size = block_size - 1.0;
+
+        if let Some((_, progress)) = clearing.filter(|(rows, _)| rows.contains(&y)) {
+            (color, size) = clearing_tile(color, size, progress, &theme);
+        }
+
         commands.spawn((
             Sprite {
-                color: theme.color(color_name),
-                custom_size: Some(Vec2::splat(BLOCK_SIZE - 1.0)),
+                color,
+                custom_size: Some(Vec2::splat(size)),
                 ..default()
             },
             Transform::from_xyz(
-                x as f32 * BLOCK_SIZE - GRID_WIDTH as f32 / 2.0 * BLOCK_SIZE,
-                y as f32 * BLOCK_SIZE - GRID_HEIGHT as f32 / 2.0 * BLOCK_SIZE,
+                x as f32 * block_size - GRID_WIDTH as f32 / 2.0 * block_size,
+                y as f32 * block_size - GRID_HEIGHT as f32 / 2.0 * block_size,
                 0.0,
             ),
             Redraw,
@@ -758,241 +1020,64 @@ fn update_sprites(
     }
 }
 
-fn handle_movement(
-    mut active_tetromino: Query<&mut Tetromino, With<Active>>,
-    grid: Res<Grid>,
-    input: Res<ButtonInput<KeyCode>>,
-) {
-    if let Ok(mut tetromino) = active_tetromino.single_mut() {
-        let mut new_tetromino = tetromino.clone();
-
-        if input.just_pressed(KeyCode::KeyA) {
-            new_tetromino.move_left();
-        }
-
-        if input.just_pressed(KeyCode::KeyD) {
-            new_tetromino.move_right();
-        }
-
-        if input.pressed(KeyCode::KeyS) {
-            new_tetromino.move_down();
-        }
-
-        if !new_tetromino
-            .occupied_tiles()
-            .iter()
-            .any(|pos| grid.tiles.contains_key(pos))
-            && !new_tetromino.is_in_ground()
-            && !new_tetromino.is_in_wall()
-        {
-            *tetromino = new_tetromino;
-        }
-    }
-}
-
-const fn get_wall_kick_offsets(kind: TetrominoKind, from: Rotation, to: Rotation) -> [IVec2; 5] {
-    match kind {
-        TetrominoKind::I => match (from, to) {
-            (Rotation::North, Rotation::East) => [
-                IVec2::ZERO,
-                IVec2::new(-2, 0),
-                IVec2::new(1, 0),
-                IVec2::new(-2, -1),
-                IVec2::new(1, 2),
-            ],
-            (Rotation::East, Rotation::North) => [
-                IVec2::ZERO,
-                IVec2::new(2, 0),
-                IVec2::new(-1, 0),
-                IVec2::new(2, 1),
-                IVec2::new(-1, -2),
-            ],
-            (Rotation::East, Rotation::South) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(2, 0),
-                IVec2::new(-1, 2),
-                IVec2::new(2, -1),
-            ],
-            (Rotation::South, Rotation::East) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(-2, 0),
-                IVec2::new(1, -2),
-                IVec2::new(-2, 1),
-            ],
-            (Rotation::South, Rotation::West) => [
-                IVec2::ZERO,
-                IVec2::new(2, 0),
-                IVec2::new(-1, 0),
-                IVec2::new(2, 1),
-                IVec2::new(-1, -2),
-            ],
-            (Rotation::West, Rotation::South) => [
-                IVec2::ZERO,
-                IVec2::new(-2, 0),
-                IVec2::new(1, 0),
-                IVec2::new(-2, -1),
-                IVec2::new(1, 2),
-            ],
-            (Rotation::West, Rotation::North) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(-2, 0),
-                IVec2::new(1, -2),
-                IVec2::new(-2, 1),
-            ],
-            (Rotation::North, Rotation::West) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(2, 0),
-                IVec2::new(-1, 2),
-                IVec2::new(2, -1),
-            ],
-            _ => unreachable!(),
-        },
-        TetrominoKind::O => [IVec2::ZERO; 5],
-        _ => match (from, to) {
-            (Rotation::North, Rotation::East) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(-1, 1),
-                IVec2::new(0, -2),
-                IVec2::new(-1, -2),
-            ],
-            (Rotation::East, Rotation::North) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(1, -1),
-                IVec2::new(0, 2),
-                IVec2::new(1, 2),
-            ],
-            (Rotation::East, Rotation::South) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(1, -1),
-                IVec2::new(0, 2),
-                IVec2::new(1, 2),
-            ],
-            (Rotation::South, Rotation::East) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(-1, 1),
-                IVec2::new(0, -2),
-                IVec2::new(-1, -2),
-            ],
-            (Rotation::South, Rotation::West) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(1, 1),
-                IVec2::new(0, -2),
-                IVec2::new(1, -2),
-            ],
-            (Rotation::West, Rotation::South) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(-1, -1),
-                IVec2::new(0, 2),
-                IVec2::new(-1, 2),
-            ],
-            (Rotation::West, Rotation::North) => [
-                IVec2::ZERO,
-                IVec2::new(1, 0),
-                IVec2::new(1, -1),
-                IVec2::new(0, 2),
-                IVec2::new(1, 2),
-            ],
-            (Rotation::North, Rotation::West) => [
-                IVec2::ZERO,
-                IVec2::new(-1, 0),
-                IVec2::new(-1, 1),
-                IVec2::new(0, -2),
-                IVec2::new(-1, -2),
-            ],
-            _ => unreachable!(),
-        },
-    }
-}
-
-fn handle_rotation(
-    mut active_tetromino: Query<&mut Tetromino, With<Active>>,
-    grid: Res<Grid>,
-    input: Res<ButtonInput<KeyCode>>,
-) {
-    if let Ok(mut tetromino) = active_tetromino.single_mut() {
-        let mut new_tetromino = tetromino.clone();
-        if input.just_pressed(KeyCode::KeyQ) {
-            new_tetromino.rotate_left();
-        } else if input.just_pressed(KeyCode::KeyE) {
-            new_tetromino.rotate_right();
+/// How a tile in a row being cleared looks `progress` of the way through
+/// the line clear delay: flashing for the first half, then dissolving.
+fn clearing_tile(color: Color, size: f32, progress: f32, theme: &Theme) -> (Color, f32) {
+    if progress < 0.5 {
+        let flash = ((progress * 8.0) as u32).is_multiple_of(2);
+        let color = if flash {
+            theme.color(ColorName::Text)
         } else {
-            return;
+            color
         };
-
-        let offsets =
-            get_wall_kick_offsets(tetromino.kind, tetromino.rotation, new_tetromino.rotation);
-
-        for offset in offsets {
-            new_tetromino.position += offset;
-
-            if !new_tetromino
-                .occupied_tiles()
-                .iter()
-                .any(|pos| grid.tiles.contains_key(pos))
-                && !new_tetromino.is_in_ground()
-                && !new_tetromino.is_in_wall()
-            {
-                *tetromino = new_tetromino;
-                break;
-            } else {
-                new_tetromino.position -= offset
-            }
-        }
+        (color, size)
+    } else {
+        let left = 1.0 - (progress - 0.5) * 2.0;
+        (color.with_alpha(left), size * left)
     }
 }
 
-fn clear_lines(mut grid: ResMut<Grid>, mut score: ResMut<Score>) {
-    let mut row_counts = [0; GRID_HEIGHT as usize];
-
-    for pos in grid.tiles.keys() {
-        if pos.y >= 0 && pos.y < GRID_HEIGHT {
-            row_counts[pos.y as usize] += 1;
-        }
+fn handle_movement(actions: Actions, mut pending: ResMut<PendingInputs>) {
+    if actions.just_pressed(Action::MoveLeft) {
+        pending.0.push(Input::MoveLeft);
     }
 
-    let full_rows: Vec<i32> = row_counts
-        .iter()
-        .enumerate()
-        .filter_map(|(y, &count)| {
-            if count == GRID_WIDTH {
-                Some(y as i32)
-            } else {
-                None
-            }
-        })
-        .collect();
+    if actions.just_pressed(Action::MoveRight) {
+        pending.0.push(Input::MoveRight);
+    }
 
-    if full_rows.is_empty() {
-        return;
+    if actions.just_pressed(Action::HardDrop) {
+        pending.0.push(Input::HardDrop);
     }
+}
 
-    for &y in &full_rows {
-        grid.tiles.retain(|pos, _| pos.y != y);
+fn handle_hold(actions: Actions, mut pending: ResMut<PendingInputs>) {
+    if actions.just_pressed(Action::Hold) {
+        pending.0.push(Input::Hold);
     }
+}
 
-    let mut new_tiles = HashMap::new();
-    for (pos, color) in grid.tiles.drain() {
-        let shift = full_rows.iter().filter(|&&y| pos.y > y).count() as i32;
-        let new_pos = pos - IVec2::Y * shift;
-        new_tiles.insert(new_pos, color);
+fn handle_rotation(actions: Actions, mut pending: ResMut<PendingInputs>) {
+    if actions.just_pressed(Action::RotateCcw) {
+        pending.0.push(Input::RotateLeft);
+    } else if actions.just_pressed(Action::RotateCw) {
+        pending.0.push(Input::RotateRight);
+    } else if actions.just_pressed(Action::Rotate180) {
+        pending.0.push(Input::Rotate180);
     }
-    grid.tiles = new_tiles;
+}
 
-    score.0 += match full_rows.len() {
-        1 => 100,
-        2 => 300,
-        3 => 500,
-        4 => 800,
-        _ => unreachable!(),
-    };
+/// Pauses the game with the pause action, and resumes it again from the
+/// pause menu.
+fn handle_pause(
+    actions: Actions,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        next_state.set(match state.get() {
+            GameState::Paused => GameState::Running,
+            _ => GameState::Paused,
+        });
+    }
 }
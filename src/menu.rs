@@ -0,0 +1,1371 @@
+//! The screens around a game: the main menu, mode select, high scores, replays,
+//! settings, the pause menu and the game-over screen. Every screen is a
+//! column of buttons that can be used with the mouse or the keyboard.
+
+use bevy::ecs::spawn::SpawnIter;
+use bevy::input::InputSystem;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+
+use catppuccin::ColorName;
+
+use tetris_rust::engine::{Delays, End, GameMode, LockReset, RandomizerKind, Replay};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::actions::{Action, Binding, Bindings};
+use crate::high_scores::{HIGH_SCORES_KEPT, HighScore, HighScores, format_date};
+use crate::replay::{ReplayPlayer, load_recent_replays, replay_label, save_replay};
+use crate::settings::{Settings, SettingsFile, settings_path};
+use crate::stats::stats_summary;
+use crate::{
+    Game, GameState, InGame, MAX_PREVIEW, PreviewCount, Redraw, Rules, SeedChoice, Theme,
+    ThemeSwitched, despawn_all, format_time,
+};
+
+/// The longest name that can be entered for a high score.
+const MAX_NAME_LENGTH: usize = 12;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ButtonPressed>()
+            .init_resource::<MenuFocus>()
+            .init_resource::<PlayerName>()
+            .init_resource::<LeaderboardMode>()
+            .init_resource::<ModeOptions>()
+            .init_resource::<Rebinding>()
+            .add_systems(Startup, load_high_scores)
+            .add_systems(
+                Update,
+                (
+                    handle_escape,
+                    update_setting_labels,
+                    button_interaction,
+                    navigate_menu,
+                    highlight_focused_button,
+                    handle_button_presses,
+                )
+                    .chain(),
+            )
+            .add_systems(Update, edit_seed.run_if(in_state(GameState::ModeSelect)))
+            .add_systems(
+                Update,
+                edit_name.run_if(in_state(GameState::GameOver).or(in_state(GameState::Finished))),
+            )
+            .add_systems(
+                Update,
+                update_leaderboard.run_if(in_state(GameState::HighScores)),
+            )
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                (
+                    despawn_all::<InGame>,
+                    despawn_all::<Redraw>,
+                    save_replay,
+                    end_game,
+                    setup_main_menu,
+                ),
+            )
+            .add_systems(OnExit(GameState::MainMenu), despawn_all::<MainMenuScreen>)
+            .add_systems(OnEnter(GameState::ModeSelect), setup_mode_select_screen)
+            .add_systems(
+                OnExit(GameState::ModeSelect),
+                despawn_all::<ModeSelectScreen>,
+            )
+            .add_systems(OnEnter(GameState::Replays), setup_replays_screen)
+            .add_systems(OnExit(GameState::Replays), despawn_all::<ReplaysScreen>)
+            .add_systems(OnEnter(GameState::HighScores), setup_high_scores_screen)
+            .add_systems(
+                OnExit(GameState::HighScores),
+                despawn_all::<HighScoresScreen>,
+            )
+            .add_systems(OnEnter(GameState::Paused), setup_pause_screen)
+            .add_systems(OnExit(GameState::Paused), despawn_all::<PauseScreen>)
+            .add_systems(OnEnter(GameState::Settings), setup_settings_screen)
+            .add_systems(OnExit(GameState::Settings), despawn_all::<SettingsScreen>)
+            .add_systems(OnEnter(GameState::Controls), setup_controls_screen)
+            .add_systems(
+                OnExit(GameState::Controls),
+                (despawn_all::<ControlsScreen>, cancel_rebinding),
+            )
+            // takes the key before anything else can act on it
+            .add_systems(
+                PreUpdate,
+                capture_rebinding
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::Controls)),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (check_high_score, setup_game_over_screen).chain(),
+            )
+            .add_systems(
+                OnExit(GameState::GameOver),
+                (save_high_score, despawn_all::<GameOverScreen>),
+            )
+            .add_systems(
+                OnEnter(GameState::Finished),
+                (check_high_score, setup_game_over_screen).chain(),
+            )
+            .add_systems(
+                OnExit(GameState::Finished),
+                (save_high_score, despawn_all::<GameOverScreen>),
+            );
+    }
+}
+
+#[derive(Component)]
+struct MainMenuScreen;
+
+#[derive(Component)]
+struct ModeSelectScreen;
+
+#[derive(Component)]
+struct HighScoresScreen;
+
+#[derive(Component)]
+struct ReplaysScreen;
+
+#[derive(Component)]
+struct PauseScreen;
+
+#[derive(Component)]
+struct SettingsScreen;
+
+#[derive(Component)]
+struct ControlsScreen;
+
+#[derive(Component)]
+struct GameOverScreen;
+
+/// A full-screen overlay that dims the game behind a column of buttons.
+#[derive(Component)]
+pub struct Menu;
+
+#[derive(Component, Clone, Copy)]
+enum ButtonAction {
+    Play,
+    StartGame(GameMode),
+    HighScores,
+    Replays,
+    WatchReplay(usize),
+    Resume,
+    Restart,
+    Settings,
+    Back,
+    MainMenu,
+    Quit,
+    ToggleTheme,
+    CycleRandomizer,
+    CycleDelays,
+    CycleLockReset,
+    TogglePartialLockOut,
+    CyclePreview,
+    ToggleEffects,
+    CycleEffectIntensity,
+    ToggleReduceMotion,
+    CycleUltraDuration,
+    ToggleFinesseRestart,
+    RandomSeed,
+    CycleLeaderboard,
+    SaveHighScore,
+    Controls,
+    Rebind(Action),
+    ResetBindings,
+}
+
+/// A button label that shows the current value of a setting.
+#[derive(Component, Clone, Copy)]
+enum SettingLabel {
+    Theme,
+    Randomizer,
+    Delays,
+    LockReset,
+    PartialLockOut,
+    Preview,
+    Effects,
+    EffectIntensity,
+    ReduceMotion,
+    UltraDuration,
+    FinesseRestart,
+    Seed,
+    Leaderboard,
+    Name,
+    Binding(Action),
+    RebindStatus,
+}
+
+/// The button that takes the digits of a chosen seed while it has focus.
+#[derive(Component)]
+pub struct SeedField;
+
+/// The button that takes the letters of the player's name while it has
+/// focus, and saves the high score when pressed.
+#[derive(Component)]
+pub struct NameField;
+
+/// A line of the leaderboard, counting from the top.
+#[derive(Component)]
+struct LeaderboardRow(usize);
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Sent when a button is clicked, or activated from the keyboard while it
+/// has focus.
+#[derive(Event)]
+struct ButtonPressed(ButtonAction);
+
+/// The button that the keyboard acts on, which follows the mouse as well.
+#[derive(Resource, Default)]
+pub struct MenuFocus(Option<Entity>);
+
+/// The action waiting for a key on the controls screen, and what came of
+/// the last key pressed for one.
+#[derive(Resource, Default)]
+struct Rebinding {
+    action: Option<Action>,
+    status: String,
+}
+
+/// The replays listed on the replays screen.
+#[derive(Resource)]
+struct ReplayList(Vec<Replay>);
+
+/// The options of the modes on the mode select screen.
+#[derive(Resource)]
+struct ModeOptions {
+    /// How long an ultra lasts.
+    ultra_duration: Duration,
+    /// Whether a training game sends pieces placed with a finesse fault
+    /// back to the top.
+    finesse_restart: bool,
+}
+
+impl Default for ModeOptions {
+    fn default() -> Self {
+        ModeOptions {
+            ultra_duration: Duration::from_secs(120),
+            finesse_restart: true,
+        }
+    }
+}
+
+const ULTRA_DURATIONS: [Duration; 4] = [
+    Duration::from_secs(60),
+    Duration::from_secs(120),
+    Duration::from_secs(180),
+    Duration::from_secs(300),
+];
+
+const EFFECT_INTENSITIES: [f32; 4] = [0.5, 1.0, 1.5, 2.0];
+
+/// The name that high scores are saved under, which carries over to the
+/// next high score.
+#[derive(Resource, Default)]
+struct PlayerName(String);
+
+/// A high score that is waiting for the player to enter their name.
+#[derive(Resource)]
+struct PendingHighScore(HighScore);
+
+/// An index into [`leaderboard_modes`] of the table on the leaderboard.
+#[derive(Resource, Default)]
+struct LeaderboardMode(usize);
+
+/// Every mode with a table on the leaderboard, including each length of
+/// ultra.
+fn leaderboard_modes() -> Vec<GameMode> {
+    GameMode::ALL
+        .into_iter()
+        .filter(|mode| !mode.is_training())
+        .flat_map(|mode| match mode {
+            GameMode::Ultra { .. } => ULTRA_DURATIONS
+                .iter()
+                .map(|&duration| GameMode::Ultra { duration })
+                .collect(),
+            mode => vec![mode],
+        })
+        .collect()
+}
+
+fn mode_label(mode: GameMode) -> String {
+    match mode {
+        GameMode::Ultra { duration } => format!("Ultra {}s", duration.as_secs()),
+        mode => mode.name().to_string(),
+    }
+}
+
+pub fn menu_background(theme: &Theme) -> BackgroundColor {
+    BackgroundColor(theme.color(ColorName::Crust).with_alpha(0.85))
+}
+
+fn menu(theme: &Theme) -> impl Bundle {
+    (
+        Menu,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        GlobalZIndex(1),
+        menu_background(theme),
+    )
+}
+
+fn menu_title(text: &str, font: &Handle<Font>, theme: &Theme) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font: font.clone(),
+            font_size: 80.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Text)),
+        TextShadow::default(),
+        Node {
+            margin: UiRect::bottom(Val::Px(16.0)),
+            ..default()
+        },
+    )
+}
+
+fn menu_text(text: impl Into<String>, font: &Handle<Font>, theme: &Theme) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font: font.clone(),
+            font_size: 32.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Text)),
+    )
+}
+
+fn menu_button(
+    action: ButtonAction,
+    label: impl Bundle,
+    font: &Handle<Font>,
+    theme: &Theme,
+) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            width: Val::Px(360.0),
+            height: Val::Px(65.0),
+            border: UiRect::all(Val::Px(6.0)),
+            margin: UiRect::top(Val::Px(16.0)),
+            // horizontally center child text
+            justify_content: JustifyContent::Center,
+            // vertically center child text
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BorderColor(Color::BLACK),
+        BorderRadius::all(Val::Px(16.0)),
+        children![(
+            label,
+            TextFont {
+                font: font.clone(),
+                font_size: 28.0,
+                ..default()
+            },
+            TextColor(theme.color(ColorName::Text)),
+            TextShadow::default(),
+        )],
+    )
+}
+
+/// Drops the game that was being played, if any, so the next one starts
+/// from scratch.
+fn end_game(mut commands: Commands) {
+    commands.remove_resource::<Game>();
+}
+
+fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    commands.spawn((
+        MainMenuScreen,
+        menu(&theme),
+        children![
+            menu_title("Tetris but Rust", &font, &theme),
+            menu_button(ButtonAction::Play, Text::new("Play"), &font, &theme),
+            menu_button(
+                ButtonAction::HighScores,
+                Text::new("High Scores"),
+                &font,
+                &theme
+            ),
+            menu_button(ButtonAction::Replays, Text::new("Replays"), &font, &theme),
+            menu_button(ButtonAction::Settings, Text::new("Settings"), &font, &theme),
+            menu_button(ButtonAction::Quit, Text::new("Quit"), &font, &theme),
+        ],
+    ));
+}
+
+fn setup_mode_select_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    let modes = GameMode::ALL.map(|mode| {
+        menu_button(
+            ButtonAction::StartGame(mode),
+            Text::new(mode.name()),
+            &font,
+            &theme,
+        )
+    });
+
+    commands.spawn((
+        ModeSelectScreen,
+        menu(&theme),
+        Children::spawn((
+            Spawn(menu_title("Select Mode", &font, &theme)),
+            SpawnIter(modes.into_iter()),
+            Spawn(menu_button(
+                ButtonAction::CycleUltraDuration,
+                (Text::default(), SettingLabel::UltraDuration),
+                &font,
+                &theme,
+            )),
+            Spawn(menu_button(
+                ButtonAction::ToggleFinesseRestart,
+                (Text::default(), SettingLabel::FinesseRestart),
+                &font,
+                &theme,
+            )),
+            Spawn((
+                SeedField,
+                menu_button(
+                    ButtonAction::RandomSeed,
+                    (Text::default(), SettingLabel::Seed),
+                    &font,
+                    &theme,
+                ),
+            )),
+            Spawn(menu_text(
+                "Type a seed to race the same pieces, press it for a random one",
+                &font,
+                &theme,
+            )),
+            Spawn(menu_button(
+                ButtonAction::Back,
+                Text::new("Back"),
+                &font,
+                &theme,
+            )),
+        )),
+    ));
+}
+
+fn setup_high_scores_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    let rows: Vec<_> = (0..HIGH_SCORES_KEPT)
+        .map(|index| (LeaderboardRow(index), menu_text("", &font, &theme)))
+        .collect();
+
+    commands.spawn((
+        HighScoresScreen,
+        menu(&theme),
+        Children::spawn((
+            Spawn(menu_title("High Scores", &font, &theme)),
+            Spawn(menu_button(
+                ButtonAction::CycleLeaderboard,
+                (Text::default(), SettingLabel::Leaderboard),
+                &font,
+                &theme,
+            )),
+            SpawnIter(rows.into_iter()),
+            Spawn(menu_button(
+                ButtonAction::Back,
+                Text::new("Back"),
+                &font,
+                &theme,
+            )),
+        )),
+    ));
+}
+
+/// Fills in the leaderboard rows from the table of the chosen mode.
+fn update_leaderboard(
+    mut rows: Query<(&mut Text, &LeaderboardRow)>,
+    high_scores: Res<HighScores>,
+    leaderboard_mode: Res<LeaderboardMode>,
+) {
+    let mode = leaderboard_modes()[leaderboard_mode.0];
+    let entries = high_scores.of_mode(mode);
+
+    for (mut text, &LeaderboardRow(index)) in &mut rows {
+        let value = match entries.get(index) {
+            Some(entry) => {
+                let result = if mode.is_race() {
+                    format_time(entry.time)
+                } else {
+                    entry.score.to_string()
+                };
+
+                format!(
+                    "{}. {}  {result}  {} lines  {}  {}  seed {}",
+                    index + 1,
+                    entry.name,
+                    entry.lines,
+                    format_time(entry.time),
+                    format_date(entry.date),
+                    entry.seed,
+                )
+            }
+            None if index == 0 => "No games yet".to_string(),
+            None => String::new(),
+        };
+
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+fn setup_replays_screen(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+    let replays = load_recent_replays();
+
+    let buttons: Vec<_> = replays
+        .iter()
+        .enumerate()
+        .map(|(index, replay)| {
+            menu_button(
+                ButtonAction::WatchReplay(index),
+                Text::new(replay_label(replay)),
+                &font,
+                &theme,
+            )
+        })
+        .collect();
+
+    let empty = replays
+        .is_empty()
+        .then(|| menu_text("No replays yet, finish a game to record one", &font, &theme));
+
+    commands.spawn((
+        ReplaysScreen,
+        menu(&theme),
+        Children::spawn((
+            Spawn(menu_title("Replays", &font, &theme)),
+            SpawnIter(empty.into_iter()),
+            SpawnIter(buttons.into_iter()),
+            Spawn(menu_button(
+                ButtonAction::Back,
+                Text::new("Back"),
+                &font,
+                &theme,
+            )),
+        )),
+    ));
+
+    commands.insert_resource(ReplayList(replays));
+}
+
+fn setup_pause_screen(mut commands: Commands, asset_server: Res<AssetServer>, theme: Res<Theme>) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    commands.spawn((
+        PauseScreen,
+        menu(&theme),
+        children![
+            menu_title("Paused", &font, &theme),
+            menu_button(ButtonAction::Resume, Text::new("Resume"), &font, &theme),
+            menu_button(ButtonAction::Restart, Text::new("Restart"), &font, &theme),
+            menu_button(ButtonAction::Settings, Text::new("Settings"), &font, &theme),
+            menu_button(ButtonAction::MainMenu, Text::new("Quit"), &font, &theme),
+        ],
+    ));
+}
+
+fn setup_settings_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    // everything else is set in the file, which is reloaded as it is edited
+    let file_note = match settings_path() {
+        Some(path) => format!("More settings in {}", path.display()),
+        None => String::new(),
+    };
+
+    commands.spawn((
+        SettingsScreen,
+        menu(&theme),
+        children![
+            menu_title("Settings", &font, &theme),
+            menu_button(
+                ButtonAction::ToggleTheme,
+                (Text::default(), SettingLabel::Theme),
+                &font,
+                &theme,
+            ),
+            // two columns, so that every setting fits on screen
+            (
+                Node {
+                    column_gap: Val::Px(20.0),
+                    ..default()
+                },
+                children![
+                    // the rules take effect from the next game
+                    (
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            ..default()
+                        },
+                        children![
+                            menu_button(
+                                ButtonAction::CycleRandomizer,
+                                (Text::default(), SettingLabel::Randomizer),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::CycleDelays,
+                                (Text::default(), SettingLabel::Delays),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::CycleLockReset,
+                                (Text::default(), SettingLabel::LockReset),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::TogglePartialLockOut,
+                                (Text::default(), SettingLabel::PartialLockOut),
+                                &font,
+                                &theme,
+                            ),
+                        ],
+                    ),
+                    (
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            ..default()
+                        },
+                        children![
+                            menu_button(
+                                ButtonAction::CyclePreview,
+                                (Text::default(), SettingLabel::Preview),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::ToggleEffects,
+                                (Text::default(), SettingLabel::Effects),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::CycleEffectIntensity,
+                                (Text::default(), SettingLabel::EffectIntensity),
+                                &font,
+                                &theme,
+                            ),
+                            menu_button(
+                                ButtonAction::ToggleReduceMotion,
+                                (Text::default(), SettingLabel::ReduceMotion),
+                                &font,
+                                &theme,
+                            ),
+                        ],
+                    ),
+                ],
+            ),
+            menu_button(ButtonAction::Controls, Text::new("Controls"), &font, &theme),
+            menu_text(file_note, &font, &theme),
+            menu_button(ButtonAction::Back, Text::new("Back"), &font, &theme),
+        ],
+    ));
+}
+
+fn setup_controls_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    let bindings: Vec<_> = Action::ALL
+        .into_iter()
+        .map(|action| {
+            menu_button(
+                ButtonAction::Rebind(action),
+                (Text::default(), SettingLabel::Binding(action)),
+                &font,
+                &theme,
+            )
+        })
+        .collect();
+
+    commands.spawn((
+        ControlsScreen,
+        menu(&theme),
+        Children::spawn((
+            Spawn(menu_title("Controls", &font, &theme)),
+            Spawn((SettingLabel::RebindStatus, menu_text("", &font, &theme))),
+            // two columns, so that every action fits on screen
+            Spawn((
+                Node {
+                    width: Val::Px(2.0 * 380.0),
+                    flex_wrap: FlexWrap::Wrap,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
+                Children::spawn(SpawnIter(bindings.into_iter())),
+            )),
+            Spawn(menu_button(
+                ButtonAction::ResetBindings,
+                Text::new("Reset Controls"),
+                &font,
+                &theme,
+            )),
+            Spawn(menu_button(
+                ButtonAction::Back,
+                Text::new("Back"),
+                &font,
+                &theme,
+            )),
+        )),
+    ));
+}
+
+/// Binds the next key pressed to the action waiting for one. ESC cancels,
+/// and BACKSPACE unbinds every key of the action. A key that is already
+/// bound to another action is refused.
+fn capture_rebinding(
+    mut input: ResMut<ButtonInput<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: SettingsFile,
+) {
+    let Some(action) = rebinding.action else {
+        return;
+    };
+
+    let Some(&key) = input.get_just_pressed().next() else {
+        return;
+    };
+
+    let mut bindings = settings.bindings.clone();
+    rebinding.status = match key {
+        KeyCode::Escape => String::new(),
+        KeyCode::Backspace => {
+            bindings.clear(action);
+            format!("{} is unbound", action.name())
+        }
+        key => match bindings.bind(action, key) {
+            Ok(()) => format!("{} bound to {}", key.name(), action.name()),
+            Err(bound) => format!("{} is already bound to {}", key.name(), bound.name()),
+        },
+    };
+    rebinding.action = None;
+
+    if bindings != settings.bindings {
+        settings.bindings = bindings;
+        settings.save();
+    }
+
+    // the key was for the binding, not for the menu
+    input.clear();
+}
+
+fn cancel_rebinding(mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+}
+
+fn setup_game_over_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    game: Res<Game>,
+    pending: Option<Res<PendingHighScore>>,
+) {
+    let font = asset_server.load("fonts/Roboto-Regular.ttf");
+
+    let (title, reason) = match game.0.end() {
+        Some(End::Completed) => ("Finished", ""),
+        Some(End::TimeUp) => ("Time Up", ""),
+        Some(end) => ("Game Over", end.name()),
+        None => ("Game Over", ""),
+    };
+
+    // a new high score asks for a name first
+    let name_field = pending.is_some().then(|| {
+        (
+            NameField,
+            menu_button(
+                ButtonAction::SaveHighScore,
+                (Text::default(), SettingLabel::Name),
+                &font,
+                &theme,
+            ),
+        )
+    });
+
+    commands.spawn((
+        GameOverScreen,
+        menu(&theme),
+        Children::spawn((
+            Spawn(menu_title(title, &font, &theme)),
+            Spawn(menu_text(reason, &font, &theme)),
+            Spawn(stats_summary(&game.0, &font, &theme)),
+            Spawn(menu_text(format!("Seed: {}", game.0.seed()), &font, &theme)),
+            SpawnIter(name_field.into_iter()),
+            Spawn(menu_button(
+                ButtonAction::Restart,
+                Text::new("Restart"),
+                &font,
+                &theme,
+            )),
+            Spawn(menu_button(
+                ButtonAction::CycleRandomizer,
+                (Text::default(), SettingLabel::Randomizer),
+                &font,
+                &theme,
+            )),
+            Spawn(menu_button(
+                ButtonAction::MainMenu,
+                Text::new("Main Menu"),
+                &font,
+                &theme,
+            )),
+        )),
+    ));
+}
+
+fn load_high_scores(mut commands: Commands) {
+    let high_scores = HighScores::load();
+
+    if let Some(name) = high_scores.last_name() {
+        commands.insert_resource(PlayerName(name.to_string()));
+    }
+    commands.insert_resource(high_scores);
+}
+
+/// Holds on to the game that just ended if it makes the leaderboard, until
+/// the player has entered their name.
+fn check_high_score(
+    mut commands: Commands,
+    game: Res<Game>,
+    rules: Res<Rules>,
+    high_scores: Res<HighScores>,
+) {
+    let mode = rules.0.mode;
+
+    // a race only counts if it was finished
+    if mode.is_race() && game.0.end() != Some(End::Completed) {
+        return;
+    }
+
+    if mode.is_training() {
+        return;
+    }
+
+    let date = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let entry = HighScore {
+        mode,
+        score: game.0.score().0,
+        lines: game.0.lines(),
+        time: game.0.time(),
+        date,
+        seed: game.0.seed(),
+        name: String::new(),
+    };
+
+    if high_scores.qualifies(&entry) {
+        commands.insert_resource(PendingHighScore(entry));
+    }
+}
+
+/// Saves the pending high score under the player's name. This also runs on
+/// leaving the game-over screen, so a high score is kept even if the name
+/// was never confirmed.
+fn save_high_score(
+    mut commands: Commands,
+    pending: Option<Res<PendingHighScore>>,
+    name: Res<PlayerName>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+
+    let name = match name.0.trim() {
+        "" => "Anonymous",
+        name => name,
+    };
+
+    high_scores.insert(HighScore {
+        name: name.to_string(),
+        ..pending.0.clone()
+    });
+    high_scores.save();
+
+    commands.remove_resource::<PendingHighScore>();
+}
+
+fn randomizer_label(kind: RandomizerKind) -> String {
+    format!("Randomizer: {}", kind.name())
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "On" } else { "Off" }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_setting_labels(
+    mut labels: Query<(&mut Text, &SettingLabel)>,
+    theme: Res<Theme>,
+    rules: Res<Rules>,
+    preview_count: Res<PreviewCount>,
+    mode_options: Res<ModeOptions>,
+    seed_choice: Res<SeedChoice>,
+    leaderboard_mode: Res<LeaderboardMode>,
+    name: Res<PlayerName>,
+    pending: Option<Res<PendingHighScore>>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+) {
+    for (mut text, label) in &mut labels {
+        let value = match label {
+            SettingLabel::Theme => format!("Theme: {}", theme.name()),
+            SettingLabel::Randomizer => randomizer_label(rules.0.randomizer),
+            SettingLabel::Delays => format!("Delays: {}", rules.0.delays.name()),
+            SettingLabel::LockReset => {
+                format!("Lock reset: {}", rules.0.lock_delay.reset.name())
+            }
+            SettingLabel::PartialLockOut => {
+                format!("Partial lock out: {}", on_off(rules.0.partial_lock_out))
+            }
+            SettingLabel::Preview => format!("Preview: {}", preview_count.0),
+            SettingLabel::Effects => format!("Effects: {}", on_off(settings.effects.enabled)),
+            SettingLabel::EffectIntensity => {
+                format!(
+                    "Effect intensity: {:.0}%",
+                    settings.effects.intensity * 100.0
+                )
+            }
+            SettingLabel::ReduceMotion => {
+                format!("Reduce motion: {}", on_off(settings.effects.reduce_motion))
+            }
+            SettingLabel::Seed => match seed_choice.0 {
+                Some(seed) => format!("Seed: {seed}"),
+                None => "Seed: Random".to_string(),
+            },
+            SettingLabel::UltraDuration => {
+                format!("Ultra length: {}s", mode_options.ultra_duration.as_secs())
+            }
+            SettingLabel::FinesseRestart => format!(
+                "Training restarts faults: {}",
+                on_off(mode_options.finesse_restart)
+            ),
+            SettingLabel::Leaderboard => {
+                format!(
+                    "Mode: {}",
+                    mode_label(leaderboard_modes()[leaderboard_mode.0])
+                )
+            }
+            SettingLabel::Name if pending.is_some() => format!("Name: {}_", name.0),
+            SettingLabel::Name => "Saved".to_string(),
+            SettingLabel::Binding(action) => {
+                format!("{}: {}", action.name(), settings.bindings.describe(*action))
+            }
+            SettingLabel::RebindStatus => match rebinding.action {
+                Some(action) => format!(
+                    "Press a key for {}, BACKSPACE to unbind or ESC to cancel",
+                    action.name()
+                ),
+                None => rebinding.status.clone(),
+            },
+        };
+
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+fn button_interaction(
+    interaction_query: Query<(Entity, &Interaction, &ButtonAction), Changed<Interaction>>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<ButtonPressed>,
+) {
+    for (entity, interaction, &action) in &interaction_query {
+        match *interaction {
+            Interaction::Hovered => focus.0 = Some(entity),
+            Interaction::None => {}
+            Interaction::Pressed => {
+                pressed.write(ButtonPressed(action));
+            }
+        }
+    }
+}
+
+/// The buttons of the open menu, top to bottom, including those laid out
+/// in rows within it.
+fn menu_buttons(
+    menus: &Query<&Children, With<Menu>>,
+    nodes: &Query<&Children>,
+    buttons: &Query<&ButtonAction>,
+) -> Vec<Entity> {
+    fn collect(
+        children: &Children,
+        nodes: &Query<&Children>,
+        buttons: &Query<&ButtonAction>,
+        order: &mut Vec<Entity>,
+    ) {
+        for child in children.iter() {
+            if buttons.contains(child) {
+                order.push(child);
+            } else if let Ok(grandchildren) = nodes.get(child) {
+                collect(grandchildren, nodes, buttons, order);
+            }
+        }
+    }
+
+    let mut order = Vec::new();
+    for children in menus {
+        collect(children, nodes, buttons, &mut order);
+    }
+    order
+}
+
+/// Moves the focus with the arrow keys or W and S, and presses the focused
+/// button with ENTER or SPACE. Only the arrow keys move away from the name
+/// field.
+fn navigate_menu(
+    input: Res<ButtonInput<KeyCode>>,
+    menus: Query<&Children, With<Menu>>,
+    nodes: Query<&Children>,
+    buttons: Query<&ButtonAction>,
+    name_field: Query<(), With<NameField>>,
+    mut focus: ResMut<MenuFocus>,
+    mut pressed: EventWriter<ButtonPressed>,
+) {
+    let order = menu_buttons(&menus, &nodes, &buttons);
+    if order.is_empty() {
+        return;
+    }
+
+    let Some(current) = focus
+        .0
+        .and_then(|entity| order.iter().position(|&e| e == entity))
+    else {
+        // a menu that just opened starts on its first button
+        focus.0 = Some(order[0]);
+        return;
+    };
+
+    // W and S are letters of the name while it is being typed
+    let (down, up) = if name_field.contains(order[current]) {
+        (vec![KeyCode::ArrowDown], vec![KeyCode::ArrowUp])
+    } else {
+        (
+            vec![KeyCode::ArrowDown, KeyCode::KeyS],
+            vec![KeyCode::ArrowUp, KeyCode::KeyW],
+        )
+    };
+
+    if input.any_just_pressed(down) {
+        focus.0 = Some(order[(current + 1) % order.len()]);
+    } else if input.any_just_pressed(up) {
+        focus.0 = Some(order[(current + order.len() - 1) % order.len()]);
+    } else if input.any_just_pressed([KeyCode::Enter, KeyCode::Space])
+        && let Some(entity) = focus.0
+        && let Ok(&action) = buttons.get(entity)
+    {
+        pressed.write(ButtonPressed(action));
+    }
+}
+
+fn highlight_focused_button(
+    focus: Res<MenuFocus>,
+    theme: Res<Theme>,
+    mut buttons: Query<(
+        Entity,
+        Ref<ButtonAction>,
+        &mut BackgroundColor,
+        &mut BorderColor,
+    )>,
+) {
+    for (entity, action, mut color, mut border_color) in &mut buttons {
+        if !focus.is_changed() && !theme.is_changed() && !action.is_added() {
+            continue;
+        }
+
+        if focus.0 == Some(entity) {
+            *color = theme.color(ColorName::Surface2).into();
+            border_color.0 = Color::WHITE;
+        } else {
+            *color = theme.color(ColorName::Subtext1).into();
+            border_color.0 = Color::BLACK;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_button_presses(
+    mut commands: Commands,
+    mut events: EventReader<ButtonPressed>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    game: Option<Res<Game>>,
+    mut rules: ResMut<Rules>,
+    mut theme: ResMut<Theme>,
+    mut preview_count: ResMut<PreviewCount>,
+    mut mode_options: ResMut<ModeOptions>,
+    mut seed_choice: ResMut<SeedChoice>,
+    replay_list: Option<Res<ReplayList>>,
+    mut leaderboard_mode: ResMut<LeaderboardMode>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: SettingsFile,
+    mut theme_switched: EventWriter<ThemeSwitched>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for &ButtonPressed(action) in events.read() {
+        match action {
+            ButtonAction::Play => game_state.set(GameState::ModeSelect),
+            ButtonAction::StartGame(mode) => {
+                rules.0.mode = match mode {
+                    GameMode::Ultra { .. } => GameMode::Ultra {
+                        duration: mode_options.ultra_duration,
+                    },
+                    GameMode::Training { .. } => GameMode::Training {
+                        restart: mode_options.finesse_restart,
+                    },
+                    mode => mode,
+                };
+                game_state.set(GameState::Running);
+            }
+            ButtonAction::HighScores => game_state.set(GameState::HighScores),
+            ButtonAction::Replays => game_state.set(GameState::Replays),
+            ButtonAction::WatchReplay(index) => {
+                if let Some(replay) = replay_list.as_ref().and_then(|list| list.0.get(index)) {
+                    commands.insert_resource(ReplayPlayer::new(replay.clone()));
+                    game_state.set(GameState::Replay);
+                }
+            }
+            ButtonAction::Resume => game_state.set(GameState::Running),
+            ButtonAction::Restart => {
+                // a game restarted from the pause menu is saved unfinished
+                commands.run_system_cached(save_replay);
+                commands.remove_resource::<Game>();
+                game_state.set(GameState::Running);
+            }
+            ButtonAction::Settings => game_state.set(GameState::Settings),
+            ButtonAction::Back => game_state.set(back_from(*state.get(), game.is_some())),
+            ButtonAction::MainMenu => game_state.set(GameState::MainMenu),
+            ButtonAction::Quit => {
+                exit.write(AppExit::Success);
+            }
+            ButtonAction::ToggleTheme => {
+                theme.toggle();
+                theme_switched.write_default();
+            }
+            ButtonAction::CycleRandomizer => {
+                let all = RandomizerKind::ALL;
+                let index = all.iter().position(|&k| k == rules.0.randomizer);
+                rules.0.randomizer = all[index.map_or(0, |i| (i + 1) % all.len())];
+            }
+            ButtonAction::CycleDelays => {
+                let all = Delays::PRESETS;
+                let index = all.iter().position(|&d| d == rules.0.delays);
+                rules.0.delays = all[index.map_or(0, |i| (i + 1) % all.len())];
+            }
+            ButtonAction::CycleLockReset => {
+                let all = LockReset::ALL;
+                let index = all.iter().position(|&r| r == rules.0.lock_delay.reset);
+                rules.0.lock_delay.reset = all[index.map_or(0, |i| (i + 1) % all.len())];
+            }
+            ButtonAction::TogglePartialLockOut => {
+                rules.0.partial_lock_out = !rules.0.partial_lock_out;
+            }
+            ButtonAction::CyclePreview => {
+                preview_count.0 = preview_count.0 % MAX_PREVIEW + 1;
+            }
+            ButtonAction::RandomSeed => seed_choice.0 = None,
+            ButtonAction::CycleLeaderboard => {
+                leaderboard_mode.0 = (leaderboard_mode.0 + 1) % leaderboard_modes().len();
+            }
+            ButtonAction::SaveHighScore => commands.run_system_cached(save_high_score),
+            ButtonAction::Controls => game_state.set(GameState::Controls),
+            ButtonAction::Rebind(action) => rebinding.action = Some(action),
+            ButtonAction::ResetBindings => {
+                settings.bindings = Bindings::default();
+                settings.save();
+                rebinding.status = "Controls reset".to_string();
+            }
+            ButtonAction::CycleUltraDuration => {
+                let index = ULTRA_DURATIONS
+                    .iter()
+                    .position(|&d| d == mode_options.ultra_duration);
+                mode_options.ultra_duration =
+                    ULTRA_DURATIONS[index.map_or(0, |i| (i + 1) % ULTRA_DURATIONS.len())];
+            }
+            ButtonAction::ToggleFinesseRestart => {
+                mode_options.finesse_restart = !mode_options.finesse_restart;
+            }
+            ButtonAction::ToggleEffects => {
+                settings.effects.enabled = !settings.effects.enabled;
+                settings.save();
+            }
+            ButtonAction::CycleEffectIntensity => {
+                let index = EFFECT_INTENSITIES
+                    .iter()
+                    .position(|&i| i == settings.effects.intensity);
+                settings.effects.intensity =
+                    EFFECT_INTENSITIES[index.map_or(0, |i| (i + 1) % EFFECT_INTENSITIES.len())];
+                settings.save();
+            }
+            ButtonAction::ToggleReduceMotion => {
+                settings.effects.reduce_motion = !settings.effects.reduce_motion;
+                settings.save();
+            }
+        }
+    }
+}
+
+/// Whether the seed or name field has focus, so that the keys typed into
+/// it shouldn't also act as shortcuts.
+pub fn typing_in_field(
+    focus: Res<MenuFocus>,
+    seed_field: Query<(), With<SeedField>>,
+    name_field: Query<(), With<NameField>>,
+) -> bool {
+    focus
+        .0
+        .is_some_and(|entity| seed_field.contains(entity) || name_field.contains(entity))
+}
+
+/// Types digits into the chosen seed, or removes the last one with
+/// BACKSPACE, while the seed button has focus.
+fn edit_seed(
+    input: Res<ButtonInput<KeyCode>>,
+    focus: Res<MenuFocus>,
+    seed_field: Query<(), With<SeedField>>,
+    mut seed_choice: ResMut<SeedChoice>,
+) {
+    if !focus.0.is_some_and(|entity| seed_field.contains(entity)) {
+        return;
+    }
+
+    for (digit, key) in DIGIT_KEYS.into_iter().enumerate() {
+        if input.just_pressed(key) {
+            let seed = match seed_choice.0 {
+                Some(seed) => seed
+                    .checked_mul(10)
+                    .and_then(|s| s.checked_add(digit as u64)),
+                None => Some(digit as u64),
+            };
+
+            // ignore digits that would overflow
+            if seed.is_some() {
+                seed_choice.0 = seed;
+            }
+        }
+    }
+
+    if input.just_pressed(KeyCode::Backspace) {
+        seed_choice.0 = seed_choice
+            .0
+            .filter(|&seed| seed >= 10)
+            .map(|seed| seed / 10);
+    }
+}
+
+/// Types letters and digits into the player's name, or removes the last
+/// one with BACKSPACE, while the name field has focus.
+fn edit_name(
+    mut keys: EventReader<KeyboardInput>,
+    focus: Res<MenuFocus>,
+    name_field: Query<(), With<NameField>>,
+    pending: Option<Res<PendingHighScore>>,
+    mut name: ResMut<PlayerName>,
+) {
+    if pending.is_none() || !focus.0.is_some_and(|entity| name_field.contains(entity)) {
+        keys.clear();
+        return;
+    }
+
+    for key in keys.read() {
+        if !key.state.is_pressed() {
+            continue;
+        }
+
+        match &key.logical_key {
+            Key::Backspace => {
+                name.0.pop();
+            }
+            Key::Character(text) => {
+                for c in text.chars().filter(|c| c.is_alphanumeric()) {
+                    if name.0.chars().count() < MAX_NAME_LENGTH {
+                        name.0.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The screen that BACK or ESC leads to from `state`. Settings opened from
+/// the pause menu go back to it while a game is in progress.
+fn back_from(state: GameState, in_game: bool) -> GameState {
+    match state {
+        GameState::Running => GameState::Paused,
+        GameState::Paused => GameState::Running,
+        GameState::Settings if in_game => GameState::Paused,
+        GameState::Controls => GameState::Settings,
+        _ => GameState::MainMenu,
+    }
+}
+
+fn handle_escape(
+    input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    game: Option<Res<Game>>,
+) {
+    // the game itself is paused with the pause action
+    if input.just_pressed(KeyCode::Escape)
+        && !matches!(state.get(), GameState::MainMenu | GameState::Running)
+    {
+        game_state.set(back_from(*state.get(), game.is_some()));
+    }
+}
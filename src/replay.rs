@@ -0,0 +1,250 @@
+//! Records every game as a [`Replay`], saves it when the game ends or is
+//! left and plays saved replays back at an adjustable speed.
+
+use bevy::prelude::*;
+
+use catppuccin::ColorName;
+
+use tetris_rust::engine::{Playback, Replay};
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+use crate::settings::Settings;
+use crate::{
+    EngineEvent, Game, GameState, InGame, Redraw, Theme, data_dir, despawn_all, format_time,
+    spawn_game_ui,
+};
+
+/// The playback speeds to step through, as multiples of real time.
+const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// How many of the most recent replays are offered for viewing.
+pub const RECENT_REPLAYS: usize = 10;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::GameOver), save_replay)
+            .add_systems(OnEnter(GameState::Finished), save_replay)
+            .add_systems(
+                OnEnter(GameState::Replay),
+                (despawn_all::<InGame>, despawn_all::<Redraw>, setup_replay).chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                advance_replay.run_if(in_state(GameState::Replay)),
+            )
+            .add_systems(
+                Update,
+                (control_replay, update_replay_text).run_if(in_state(GameState::Replay)),
+            );
+    }
+}
+
+/// The game being played, recorded tick by tick.
+#[derive(Resource)]
+pub struct Recording(pub Replay);
+
+/// The replay being watched and how it is being played back.
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    playback: Playback,
+    /// An index into [`SPEEDS`].
+    speed: usize,
+    paused: bool,
+    /// Set to play back a single tick while paused.
+    step: bool,
+    /// Ticks owed to the playback at speeds other than real time.
+    owed: f32,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            playback: Playback::new(replay),
+            speed: SPEEDS.iter().position(|&speed| speed == 1.0).unwrap(),
+            paused: false,
+            step: false,
+            owed: 0.0,
+        }
+    }
+}
+
+fn replays_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("replays"))
+}
+
+/// Saves the game being recorded, whether it ended or was left unfinished,
+/// and stops recording it.
+pub fn save_replay(mut commands: Commands, recording: Option<Res<Recording>>) {
+    let Some(recording) = recording else {
+        return;
+    };
+    commands.remove_resource::<Recording>();
+
+    let Some(dir) = replays_dir() else {
+        warn!("no data directory to save the replay in");
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!("{timestamp}.replay"));
+
+    if let Err(error) =
+        fs::create_dir_all(&dir).and_then(|_| fs::write(&path, recording.0.to_bytes()))
+    {
+        warn!("could not save the replay to {}: {error}", path.display());
+    }
+}
+
+/// Loads the most recent saved replays, newest first. Files that can't be
+/// read are skipped with a warning.
+pub fn load_recent_replays() -> Vec<Replay> {
+    let Some(dir) = replays_dir() else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "replay"))
+            .collect(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(error) => {
+            warn!("could not list replays in {}: {error}", dir.display());
+            return Vec::new();
+        }
+    };
+
+    // the file names are timestamps
+    paths.sort_by(|a, b| b.cmp(a));
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let replay = fs::read(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|bytes| Replay::from_bytes(&bytes).map_err(|error| error.to_string()));
+
+            replay
+                .inspect_err(|error| warn!("skipping replay {}: {error}", path.display()))
+                .ok()
+        })
+        .take(RECENT_REPLAYS)
+        .collect()
+}
+
+/// Describes a replay for the list of replays to watch.
+pub fn replay_label(replay: &Replay) -> String {
+    format!(
+        "{} {} (seed {})",
+        replay.ruleset.mode.name(),
+        format_time(replay.tick * replay.ticks),
+        replay.seed
+    )
+}
+
+#[derive(Component)]
+struct ReplayText;
+
+fn setup_replay(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    player: Res<ReplayPlayer>,
+    settings: Res<Settings>,
+) {
+    let replay = player.playback.replay();
+    commands.insert_resource(Game(replay.engine()));
+
+    spawn_game_ui(
+        &mut commands,
+        &asset_server,
+        &theme,
+        settings.block_size,
+        replay.seed,
+    );
+
+    commands.spawn((
+        InGame,
+        ReplayText,
+        Text::default(),
+        TextFont {
+            font: asset_server.load("fonts/Roboto-Regular.ttf"),
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Subtext1)),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            left: Val::Px(32.0),
+            ..default()
+        },
+    ));
+}
+
+/// Pauses with SPACE, steps a single tick with RIGHT while paused, and
+/// changes the speed with UP and DOWN.
+fn control_replay(input: Res<ButtonInput<KeyCode>>, mut player: ResMut<ReplayPlayer>) {
+    if input.just_pressed(KeyCode::Space) {
+        player.paused = !player.paused;
+    }
+
+    if input.just_pressed(KeyCode::ArrowRight) && player.paused {
+        player.step = true;
+    }
+
+    if input.just_pressed(KeyCode::ArrowUp) {
+        player.speed = (player.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    if input.just_pressed(KeyCode::ArrowDown) {
+        player.speed = player.speed.saturating_sub(1);
+    }
+}
+
+fn advance_replay(
+    mut player: ResMut<ReplayPlayer>,
+    mut game: ResMut<Game>,
+    mut events: EventWriter<EngineEvent>,
+) {
+    let player = &mut *player;
+
+    let ticks = if player.paused {
+        std::mem::take(&mut player.step) as u32
+    } else {
+        player.owed += SPEEDS[player.speed];
+        let ticks = player.owed.floor();
+        player.owed -= ticks;
+        ticks as u32
+    };
+
+    for _ in 0..ticks {
+        let tick_events = player.playback.step(&mut game.0);
+        events.write_batch(tick_events.into_iter().map(EngineEvent));
+    }
+}
+
+fn update_replay_text(player: Res<ReplayPlayer>, mut query: Query<&mut Text, With<ReplayText>>) {
+    let status = if player.playback.is_finished() {
+        "Finished"
+    } else if player.paused {
+        "Paused"
+    } else {
+        "Playing"
+    };
+
+    for mut text in &mut query {
+        **text = format!(
+            "Replay: {status} at {}x\nSPACE to pause, RIGHT to step, UP/DOWN for speed, ESC to leave",
+            SPEEDS[player.speed]
+        );
+    }
+}
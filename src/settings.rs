@@ -0,0 +1,794 @@
+//! The player's settings, kept in a RON file in the config directory. The
+//! file is written whenever a setting is changed in game, and read again
+//! whenever it is edited by hand while the game is running.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use tetris_rust::engine::{Delays, Handling, Leveling, LockDelay, LockReset, RandomizerKind};
+
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fmt, fs, io};
+
+use crate::actions::{Binding, Bindings, GamepadBindings};
+use crate::effects::Effects;
+use crate::{
+    Game, GameState, InGame, MAX_PREVIEW, PlayerHandling, PreviewCount, Redraw, Rules, Theme,
+    ThemeSwitched, spawn_game_ui,
+};
+
+/// How often the settings file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = Settings::load();
+
+        // size the window before it opens, rather than on the first frame
+        let world = app.world_mut();
+        if let Ok(mut window) = world
+            .query_filtered::<&mut Window, With<PrimaryWindow>>()
+            .single_mut(world)
+        {
+            let WindowSize { width, height } = settings.window;
+            window.resolution.set(width, height);
+        }
+
+        app.insert_resource(settings)
+            .init_resource::<SettingsWatch>()
+            .add_systems(
+                Update,
+                (
+                    watch_settings,
+                    apply_settings,
+                    store_settings,
+                    relayout_game
+                        .run_if(resource_exists::<Game>.and(not(in_state(GameState::Replay)))),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub window: WindowSize,
+    /// The size of a tile of the board, in pixels.
+    pub block_size: f32,
+    pub theme: Theme,
+    #[serde(with = "randomizer_name")]
+    pub randomizer: RandomizerKind,
+    /// How many upcoming pieces are shown.
+    pub preview: usize,
+    pub gravity: Gravity,
+    pub delays: Timing,
+    pub lock_delay: Locking,
+    /// Ends the game when any part of a piece locks above the skyline.
+    pub partial_lock_out: bool,
+    pub handling: Feel,
+    pub bindings: Bindings,
+    /// How any controller without bindings of its own is bound.
+    pub gamepad: GamepadBindings,
+    /// Bindings for particular controllers, by the name they report.
+    pub controllers: BTreeMap<String, GamepadBindings>,
+    pub effects: Effects,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window: WindowSize::default(),
+            block_size: 32.0,
+            theme: Theme::default(),
+            randomizer: RandomizerKind::default(),
+            preview: PreviewCount::default().0,
+            gravity: Leveling::default().into(),
+            delays: Delays::default().into(),
+            lock_delay: LockDelay::default().into(),
+            partial_lock_out: false,
+            handling: Handling::default().into(),
+            bindings: Bindings::default(),
+            gamepad: GamepadBindings::default(),
+            controllers: BTreeMap::new(),
+            effects: Effects::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WindowSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WindowSize {
+    fn default() -> Self {
+        WindowSize {
+            width: 1920.0,
+            height: 1280.0,
+        }
+    }
+}
+
+/// Where the gravity curve starts and how quickly it climbs.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Gravity {
+    pub start_level: u32,
+    /// Lines to clear before the level, and with it gravity, goes up.
+    pub lines_per_level: u32,
+}
+
+impl From<Leveling> for Gravity {
+    fn from(leveling: Leveling) -> Self {
+        Gravity {
+            start_level: leveling.start_level,
+            lines_per_level: leveling.lines_per_level,
+        }
+    }
+}
+
+impl From<Gravity> for Leveling {
+    fn from(gravity: Gravity) -> Self {
+        Leveling {
+            start_level: gravity.start_level,
+            lines_per_level: gravity.lines_per_level,
+        }
+    }
+}
+
+/// The pauses between one piece locking and the next spawning, in
+/// milliseconds.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Timing {
+    pub line_clear: u64,
+    /// Entry delay, or ARE.
+    pub entry: u64,
+}
+
+impl From<Delays> for Timing {
+    fn from(delays: Delays) -> Self {
+        Timing {
+            line_clear: delays.line_clear.as_millis() as u64,
+            entry: delays.entry.as_millis() as u64,
+        }
+    }
+}
+
+impl From<Timing> for Delays {
+    fn from(timing: Timing) -> Self {
+        // the presets are whole frames, which milliseconds can't hold
+        Delays::PRESETS
+            .into_iter()
+            .find(|&preset| Timing::from(preset) == timing)
+            .unwrap_or(Delays {
+                line_clear: Duration::from_millis(timing.line_clear),
+                entry: Duration::from_millis(timing.entry),
+            })
+    }
+}
+
+/// How long a grounded piece waits before it locks, in milliseconds, and
+/// what puts the wait off.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Locking {
+    pub duration: u64,
+    /// How many times moving can restart the wait, with the move reset.
+    pub max_resets: u32,
+    #[serde(with = "lock_reset_name")]
+    pub reset: LockReset,
+}
+
+impl From<LockDelay> for Locking {
+    fn from(delay: LockDelay) -> Self {
+        Locking {
+            duration: delay.duration.as_millis() as u64,
+            max_resets: delay.max_resets,
+            reset: delay.reset,
+        }
+    }
+}
+
+impl From<Locking> for LockDelay {
+    fn from(locking: Locking) -> Self {
+        LockDelay {
+            duration: Duration::from_millis(locking.duration),
+            max_resets: locking.max_resets,
+            reset: locking.reset,
+        }
+    }
+}
+
+/// How held inputs behave, with times in milliseconds.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Feel {
+    /// Delayed Auto Shift.
+    pub das: u64,
+    /// Auto Repeat Rate, where 0 shifts straight to the wall.
+    pub arr: u64,
+    /// How many times faster than gravity soft drop falls, where 0 drops
+    /// straight to the floor.
+    pub soft_drop_factor: u32,
+    pub das_cut: u64,
+}
+
+impl From<Handling> for Feel {
+    fn from(handling: Handling) -> Self {
+        Feel {
+            das: handling.das.as_millis() as u64,
+            arr: handling.arr.as_millis() as u64,
+            soft_drop_factor: match handling.soft_drop_factor {
+                u32::MAX => 0,
+                factor => factor,
+            },
+            das_cut: handling.das_cut.as_millis() as u64,
+        }
+    }
+}
+
+impl From<Feel> for Handling {
+    fn from(feel: Feel) -> Self {
+        Handling {
+            das: Duration::from_millis(feel.das),
+            arr: Duration::from_millis(feel.arr),
+            soft_drop_factor: match feel.soft_drop_factor {
+                0 => u32::MAX,
+                factor => factor,
+            },
+            das_cut: Duration::from_millis(feel.das_cut),
+        }
+    }
+}
+
+/// Finds which of `all` is called `name`, for settings stored by the name
+/// they are shown with in game.
+fn by_name<T: Copy>(
+    what: &str,
+    name: &str,
+    all: &[T],
+    name_of: fn(T) -> &'static str,
+) -> Result<T, String> {
+    all.iter()
+        .copied()
+        .find(|&value| name_of(value) == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = all
+                .iter()
+                .map(|&value| format!("\"{}\"", name_of(value)))
+                .collect();
+            format!(
+                "unknown {what} \"{name}\", expected one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+/// Reads and writes the randomizer by the name it is shown with in game.
+mod randomizer_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        kind: &RandomizerKind,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(kind.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<RandomizerKind, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        by_name(
+            "randomizer",
+            &name,
+            &RandomizerKind::ALL,
+            RandomizerKind::name,
+        )
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reads and writes the lock reset by the name it is shown with in game.
+mod lock_reset_name {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(reset: &LockReset, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(reset.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<LockReset, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        by_name("lock reset", &name, &LockReset::ALL, LockReset::name)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    /// The file isn't valid RON, or doesn't have the shape of the settings.
+    Parse(ron::error::SpannedError),
+    /// A setting is out of range.
+    Invalid(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(error) => write!(f, "{error}"),
+            SettingsError::Parse(error) => write!(f, "{error}"),
+            SettingsError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+pub fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tetris-rust").join("settings.ron"))
+}
+
+impl Settings {
+    /// Loads the settings file, writing one with the defaults if there is
+    /// none yet. Settings that can't be read fall back to the defaults,
+    /// leaving the file alone for the player to fix.
+    pub fn load() -> Self {
+        let Some(path) = settings_path() else {
+            return Settings::default();
+        };
+
+        match Settings::read(&path) {
+            Ok(settings) => settings,
+            Err(SettingsError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+                let settings = Settings::default();
+                settings.save();
+                settings
+            }
+            Err(error) => {
+                error!("using the default settings, {}: {error}", path.display());
+                Settings::default()
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, SettingsError> {
+        let text = fs::read_to_string(path).map_err(SettingsError::Io)?;
+        Settings::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SettingsError> {
+        let settings: Settings = ron::from_str(text).map_err(SettingsError::Parse)?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("settings are always serializable")
+    }
+
+    fn save(&self) {
+        let Some(path) = settings_path() else {
+            warn!("no config directory to save settings in");
+            return;
+        };
+
+        let result = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::write(&path, self.to_ron()));
+
+        if let Err(error) = result {
+            warn!("could not save settings to {}: {error}", path.display());
+        }
+    }
+
+    /// The bindings of the controller called `name`.
+    pub fn gamepad_bindings(&self, name: &str) -> &GamepadBindings {
+        self.controllers.get(name).unwrap_or(&self.gamepad)
+    }
+
+    fn validate(&self) -> Result<(), SettingsError> {
+        let invalid = |message: String| Err(SettingsError::Invalid(message));
+
+        let WindowSize { width, height } = self.window;
+        if !(640.0..=7680.0).contains(&width) || !(480.0..=4320.0).contains(&height) {
+            return invalid(format!(
+                "window must be between 640x480 and 7680x4320, not {width}x{height}"
+            ));
+        }
+
+        if !(8.0..=96.0).contains(&self.block_size) {
+            return invalid(format!(
+                "block_size must be between 8 and 96, not {}",
+                self.block_size
+            ));
+        }
+
+        if !(1..=MAX_PREVIEW).contains(&self.preview) {
+            return invalid(format!(
+                "preview must be between 1 and {MAX_PREVIEW}, not {}",
+                self.preview
+            ));
+        }
+
+        if !(1..=30).contains(&self.gravity.start_level) {
+            return invalid(format!(
+                "gravity.start_level must be between 1 and 30, not {}",
+                self.gravity.start_level
+            ));
+        }
+
+        if self.gravity.lines_per_level == 0 {
+            return invalid("gravity.lines_per_level must be at least 1".to_string());
+        }
+
+        let Timing { line_clear, entry } = self.delays;
+        if line_clear > 2000 || entry > 2000 {
+            return invalid(format!(
+                "delays must be at most 2000 milliseconds, not {line_clear} and {entry}"
+            ));
+        }
+
+        let Locking {
+            duration,
+            max_resets,
+            ..
+        } = self.lock_delay;
+        if duration > 5000 || max_resets > 100 {
+            return invalid(format!(
+                "lock_delay.duration must be at most 5000 milliseconds and \
+                 lock_delay.max_resets at most 100, not {duration} and {max_resets}"
+            ));
+        }
+
+        let Feel {
+            das,
+            arr,
+            soft_drop_factor,
+            das_cut,
+        } = self.handling;
+        if das > 1000 || arr > 500 || das_cut > 500 {
+            return invalid(format!(
+                "handling.das must be at most 1000 milliseconds and handling.arr and \
+                 handling.das_cut at most 500, not {das}, {arr} and {das_cut}"
+            ));
+        }
+
+        if soft_drop_factor > 100 {
+            return invalid(format!(
+                "handling.soft_drop_factor must be at most 100, not {soft_drop_factor}"
+            ));
+        }
+
+        if !(0.25..=2.0).contains(&self.effects.intensity) {
+            return invalid(format!(
+                "effects.intensity must be between 0.25 and 2, not {}",
+                self.effects.intensity
+            ));
+        }
+
+        if let Some((key, first, second)) = self.bindings.conflict() {
+            return invalid(format!(
+                "{} is bound to both {first:?} and {second:?}",
+                key.name()
+            ));
+        }
+
+        let gamepads = [("gamepad", &self.gamepad)].into_iter().chain(
+            self.controllers
+                .iter()
+                .map(|(name, bindings)| (name.as_str(), bindings)),
+        );
+        for (name, bindings) in gamepads {
+            if !(0.1..=0.9).contains(&bindings.deadzone) {
+                return invalid(format!(
+                    "the deadzone of {name} must be between 0.1 and 0.9, not {}",
+                    bindings.deadzone
+                ));
+            }
+
+            if let Some((button, first, second)) = bindings.buttons.conflict() {
+                return invalid(format!(
+                    "{} on {name} is bound to both {first:?} and {second:?}",
+                    button.name()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// When the settings file was last seen to change.
+#[derive(Resource)]
+struct SettingsWatch {
+    timer: Timer,
+    modified: Option<SystemTime>,
+}
+
+impl Default for SettingsWatch {
+    fn default() -> Self {
+        SettingsWatch {
+            timer: Timer::new(WATCH_INTERVAL, TimerMode::Repeating),
+            modified: settings_modified(),
+        }
+    }
+}
+
+impl SettingsWatch {
+    /// Saves `settings` without the watch reloading the file just written.
+    fn save(&mut self, settings: &Settings) {
+        settings.save();
+        self.modified = settings_modified();
+    }
+}
+
+/// The settings, for systems that change them and save them to the file.
+#[derive(SystemParam)]
+pub struct SettingsFile<'w> {
+    settings: ResMut<'w, Settings>,
+    watch: ResMut<'w, SettingsWatch>,
+}
+
+impl SettingsFile<'_> {
+    pub fn save(&mut self) {
+        self.watch.save(&self.settings);
+    }
+}
+
+impl Deref for SettingsFile<'_> {
+    type Target = Settings;
+
+    fn deref(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+impl DerefMut for SettingsFile<'_> {
+    fn deref_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+}
+
+fn settings_modified() -> Option<SystemTime> {
+    settings_path()
+        .and_then(|path| fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
+
+/// Reloads the settings file once it has been edited. A file that can't be
+/// read keeps the current settings until it is fixed.
+fn watch_settings(
+    time: Res<Time>,
+    mut watch: ResMut<SettingsWatch>,
+    mut settings: ResMut<Settings>,
+) {
+    if !watch.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let modified = settings_modified();
+    if modified == watch.modified {
+        return;
+    }
+    watch.modified = modified;
+
+    let Some(path) = settings_path() else {
+        return;
+    };
+
+    match Settings::read(&path) {
+        Ok(new) => {
+            if settings.set_if_neq(new) {
+                info!("reloaded settings from {}", path.display());
+            }
+        }
+        // a file that was deleted is written again on the next change
+        Err(SettingsError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => error!("keeping the current settings, {}: {error}", path.display()),
+    }
+}
+
+/// Brings the game in line with the settings whenever they change.
+fn apply_settings(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut theme: ResMut<Theme>,
+    mut theme_switched: EventWriter<ThemeSwitched>,
+    mut rules: ResMut<Rules>,
+    mut preview_count: ResMut<PreviewCount>,
+    mut handling: ResMut<PlayerHandling>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if let Ok(mut window) = windows.single_mut() {
+        let WindowSize { width, height } = settings.window;
+        if window.resolution.width() != width || window.resolution.height() != height {
+            window.resolution.set(width, height);
+        }
+    }
+
+    if *theme != settings.theme {
+        *theme = settings.theme;
+        theme_switched.write_default();
+    }
+
+    let randomizer = settings.randomizer;
+    let leveling = settings.gravity.into();
+    let delays = settings.delays.into();
+    let lock_delay = settings.lock_delay.into();
+    if rules.0.randomizer != randomizer
+        || rules.0.leveling != leveling
+        || rules.0.delays != delays
+        || rules.0.lock_delay != lock_delay
+        || rules.0.partial_lock_out != settings.partial_lock_out
+    {
+        rules.0.randomizer = randomizer;
+        rules.0.leveling = leveling;
+        rules.0.delays = delays;
+        rules.0.lock_delay = lock_delay;
+        rules.0.partial_lock_out = settings.partial_lock_out;
+    }
+
+    preview_count.set_if_neq(PreviewCount(settings.preview));
+    // the handling of a game is recorded in its replay, so it changes from
+    // the next game
+    handling.set_if_neq(PlayerHandling(settings.handling.into()));
+}
+
+/// Writes settings changed in game back to the file.
+fn store_settings(
+    mut settings: ResMut<Settings>,
+    mut watch: ResMut<SettingsWatch>,
+    theme: Res<Theme>,
+    rules: Res<Rules>,
+    preview_count: Res<PreviewCount>,
+) {
+    if !theme.is_changed() && !rules.is_changed() && !preview_count.is_changed() {
+        return;
+    }
+
+    let new = Settings {
+        theme: *theme,
+        randomizer: rules.0.randomizer,
+        preview: preview_count.0,
+        gravity: rules.0.leveling.into(),
+        delays: rules.0.delays.into(),
+        lock_delay: rules.0.lock_delay.into(),
+        partial_lock_out: rules.0.partial_lock_out,
+        ..settings.clone()
+    };
+
+    if settings.set_if_neq(new) {
+        watch.save(&settings);
+    }
+}
+
+/// Lays the board out again when the block size changes mid-game.
+#[allow(clippy::too_many_arguments)]
+fn relayout_game(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut block_size: Local<Option<f32>>,
+    asset_server: Res<AssetServer>,
+    theme: Res<Theme>,
+    game: Res<Game>,
+    in_game: Query<Entity, With<InGame>>,
+    redraw: Query<Entity, With<Redraw>>,
+) {
+    let previous = block_size.replace(settings.block_size);
+    if previous.is_none_or(|size| size == settings.block_size) {
+        return;
+    }
+
+    for entity in in_game.iter().chain(&redraw) {
+        commands.entity(entity).despawn();
+    }
+
+    spawn_game_ui(
+        &mut commands,
+        &asset_server,
+        &theme,
+        settings.block_size,
+        game.0.seed(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::Action;
+
+    #[test]
+    fn round_trips_through_ron() {
+        let settings = Settings {
+            theme: Theme::Light,
+            randomizer: RandomizerKind::Nes,
+            lock_delay: Locking {
+                reset: LockReset::Step,
+                ..LockDelay::default().into()
+            },
+            ..Settings::default()
+        };
+
+        let parsed = Settings::from_ron(&settings.to_ron()).unwrap();
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn converts_handling() {
+        let handling = Handling {
+            arr: Duration::ZERO,
+            soft_drop_factor: u32::MAX,
+            ..Handling::default()
+        };
+
+        let feel = Feel::from(handling);
+        assert_eq!(feel.arr, 0);
+        assert_eq!(feel.soft_drop_factor, 0);
+        assert_eq!(Handling::from(feel), handling);
+    }
+
+    #[test]
+    fn fills_in_missing_settings() {
+        let settings = Settings::from_ron("(block_size: 24.0, bindings: {Hold: [KeyH]})").unwrap();
+
+        assert_eq!(settings.block_size, 24.0);
+        assert_eq!(settings.bindings.keys(Action::Hold), [KeyCode::KeyH]);
+        assert_eq!(
+            settings.bindings.keys(Action::MoveLeft),
+            Bindings::default().keys(Action::MoveLeft)
+        );
+        assert_eq!(settings.window, WindowSize::default());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let error = |text| Settings::from_ron(text).unwrap_err().to_string();
+
+        assert_eq!(
+            error("(block_size: 200.0)"),
+            "block_size must be between 8 and 96, not 200"
+        );
+        assert_eq!(
+            error("(bindings: {Hold: [KeyA]})"),
+            "A is bound to both MoveLeft and Hold"
+        );
+        assert_eq!(
+            error(r#"(controllers: {"Pad": (deadzone: 0.0)})"#),
+            "the deadzone of Pad must be between 0.1 and 0.9, not 0"
+        );
+        assert_eq!(
+            error("(handling: (das: 2000, arr: 0, soft_drop_factor: 20, das_cut: 0))"),
+            "handling.das must be at most 1000 milliseconds and handling.arr and \
+             handling.das_cut at most 500, not 2000, 0 and 0"
+        );
+        assert_eq!(
+            error("(effects: (intensity: 5.0))"),
+            "effects.intensity must be between 0.25 and 2, not 5"
+        );
+        assert!(error("(randomizer: \"8-bag\")").contains("unknown randomizer \"8-bag\""));
+        assert!(
+            error(r#"(lock_delay: (duration: 500, max_resets: 15, reset: "Slide"))"#)
+                .contains(r#"unknown lock reset "Slide", expected one of "Move", "Step", "Entry""#)
+        );
+        assert!(error("(blocksize: 24.0)").contains("blocksize"));
+    }
+}
@@ -0,0 +1,149 @@
+//! A live panel of stats while playing or watching a replay, and the same
+//! stats summed up once the game ends.
+
+use bevy::prelude::*;
+
+use catppuccin::ColorName;
+
+use tetris_rust::engine::{Clear, Engine, Spin};
+
+use crate::{Game, GameState, InGame, Theme, ThemeSwitched, clear_label, format_time};
+
+/// Every kind of clear, in the order the distribution lists them.
+const CLEARS: [Clear; 11] = [
+    Clear::new(1, Spin::None),
+    Clear::new(2, Spin::None),
+    Clear::new(3, Spin::None),
+    Clear::new(4, Spin::None),
+    Clear::new(0, Spin::Mini),
+    Clear::new(1, Spin::Mini),
+    Clear::new(2, Spin::Mini),
+    Clear::new(0, Spin::Full),
+    Clear::new(1, Spin::Full),
+    Clear::new(2, Spin::Full),
+    Clear::new(3, Spin::Full),
+];
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                update_stats_panel
+                    .run_if(in_state(GameState::Running).or(in_state(GameState::Replay))),
+                recolor_stats,
+            ),
+        );
+    }
+}
+
+#[derive(Component)]
+struct StatsPanel;
+
+#[derive(Component)]
+struct StatsText;
+
+#[derive(Component)]
+struct SummaryText;
+
+/// Counts, rates and time, one line each.
+fn stats_lines(engine: &Engine) -> Vec<String> {
+    let stats = engine.stats();
+
+    vec![
+        format!("Lines: {}", engine.lines()),
+        format!("Pieces: {}", engine.pieces()),
+        format!("PPS: {:.2}", engine.pieces_per_second()),
+        format!("APM: {:.1}", engine.actions_per_minute()),
+        format!("KPP: {:.2}", engine.inputs_per_piece()),
+        format!("Time: {}", format_time(engine.time())),
+        format!("Longest combo: {}", stats.longest_combo),
+        format!("Finesse faults: {}", stats.finesse_faults),
+    ]
+}
+
+/// How many of each kind of clear were made, leaving out those that
+/// weren't made at all.
+fn clear_lines(engine: &Engine) -> Vec<String> {
+    CLEARS
+        .into_iter()
+        .map(|clear| (clear, engine.stats().clears(clear)))
+        .filter(|&(_, count)| count > 0)
+        .map(|(clear, count)| format!("{}: {count}", clear_label(clear)))
+        .collect()
+}
+
+fn stats_font(font: &Handle<Font>, theme: &Theme) -> impl Bundle {
+    (
+        TextFont {
+            font: font.clone(),
+            font_size: 22.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Subtext1)),
+    )
+}
+
+pub fn spawn_stats_panel(commands: &mut Commands, font: &Handle<Font>, theme: &Theme) {
+    commands.spawn((
+        InGame,
+        StatsPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(32.0),
+            right: Val::Px(32.0),
+            ..default()
+        },
+        children![(StatsText, Text::default(), stats_font(font, theme))],
+    ));
+}
+
+fn update_stats_panel(game: Res<Game>, mut query: Query<&mut Text, With<StatsText>>) {
+    let mut lines = stats_lines(&game.0);
+    lines.extend(clear_lines(&game.0));
+
+    for mut text in &mut query {
+        **text = lines.join("\n");
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn recolor_stats(
+    theme: Res<Theme>,
+    mut events: EventReader<ThemeSwitched>,
+    mut query: Query<&mut TextColor, Or<(With<StatsText>, With<SummaryText>)>>,
+) {
+    for _ in events.read() {
+        for mut color in &mut query {
+            *color = TextColor(theme.color(ColorName::Subtext1));
+        }
+    }
+}
+
+/// The stats of a finished game side by side with its clears, for the end
+/// of game screen.
+pub fn stats_summary(engine: &Engine, font: &Handle<Font>, theme: &Theme) -> impl Bundle {
+    let clears = clear_lines(engine);
+    let clears = if clears.is_empty() {
+        "No clears".to_string()
+    } else {
+        clears.join("\n")
+    };
+
+    (
+        Node {
+            column_gap: Val::Px(48.0),
+            ..default()
+        },
+        children![
+            (
+                SummaryText,
+                Text::new(stats_lines(engine).join("\n")),
+                stats_font(font, theme)
+            ),
+            (SummaryText, Text::new(clears), stats_font(font, theme)),
+        ],
+    )
+}
@@ -0,0 +1,115 @@
+//! Feedback for training games: whether each piece was placed with good
+//! finesse, and if not, the moves that would have placed it.
+
+use bevy::prelude::*;
+
+use catppuccin::ColorName;
+
+use tetris_rust::engine::{self, GameMode};
+
+use crate::{EngineEvent, Game, GameState, InGame, Theme};
+
+pub struct TrainerPlugin;
+
+impl Plugin for TrainerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_trainer_text
+                .run_if(in_state(GameState::Running).or(in_state(GameState::Replay))),
+        );
+    }
+}
+
+#[derive(Component)]
+struct TrainerText;
+
+pub fn spawn_trainer_text(commands: &mut Commands, font: &Handle<Font>, theme: &Theme) {
+    commands.spawn((
+        InGame,
+        TrainerText,
+        Text::default(),
+        TextFont {
+            font: font.clone(),
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(theme.color(ColorName::Text)),
+        TextLayout::new_with_justify(JustifyText::Right),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(32.0),
+            right: Val::Px(32.0),
+            ..default()
+        },
+    ));
+}
+
+fn update_trainer_text(
+    mut events: EventReader<EngineEvent>,
+    game: Res<Game>,
+    theme: Res<Theme>,
+    mut query: Query<(&mut Text, &mut TextColor), With<TrainerText>>,
+) {
+    let GameMode::Training { restart } = game.0.ruleset().mode else {
+        events.clear();
+        return;
+    };
+
+    let mut judged = false;
+    let mut feedback = None;
+
+    for EngineEvent(event) in events.read() {
+        match event {
+            engine::Event::FinesseFault => {
+                judged = true;
+
+                let Some(fault) = game.0.finesse_fault() else {
+                    continue;
+                };
+
+                let moves = if fault.optimal.is_empty() {
+                    "just drop it".to_string()
+                } else {
+                    fault
+                        .optimal
+                        .iter()
+                        .map(|step| step.name())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+
+                let retry = if restart { "\nTry again" } else { "" };
+                feedback = Some((
+                    format!(
+                        "Finesse fault: {} moves for {}\nBest: {moves}{retry}",
+                        fault.used,
+                        fault.optimal.len()
+                    ),
+                    ColorName::Red,
+                ));
+            }
+            engine::Event::FinesseClean => {
+                judged = true;
+                feedback = Some(("Good finesse".to_string(), ColorName::Green));
+            }
+            engine::Event::PieceLocked(_) => {
+                // tucks and spins are left to the player
+                if !judged {
+                    feedback = Some(("Not judged".to_string(), ColorName::Subtext0));
+                }
+                judged = false;
+            }
+            _ => {}
+        }
+    }
+
+    let Some((feedback, color)) = feedback else {
+        return;
+    };
+
+    for (mut text, mut text_color) in &mut query {
+        **text = feedback.clone();
+        *text_color = TextColor(theme.color(color));
+    }
+}